@@ -1,37 +1,58 @@
 
 use std::collections::VecDeque;
 
-use piston_window::{Context, G2d, Line, Transformed, types, Rectangle};
-use piston_window::rectangle;
-use piston_window::grid::Grid;
-
-use colors::GREY;
-use tetromino::{Piece, Block};
+use tetromino::{Piece, Block, Shape};
 use settings::*;
 
 type GridRow = [CellState; WIDTH_IN_BLOCKS as usize];
 
+// The playfield. Rows 0 to HEIGHT_IN_BLOCKS - 1 are the visible matrix, and `hidden_rows` more
+// rows sit above it at negative y for pieces to spawn and stack into.
 pub struct Board {
+    hidden_rows: i32,
     grid: VecDeque<GridRow>
 }
 impl Board {
-    pub fn new() -> Board {
+    pub fn new(hidden_rows: i32) -> Board {
         Board {
-            grid: Self::create_empty_grid()
+            hidden_rows: hidden_rows,
+            grid: Self::create_empty_grid(hidden_rows)
         }
     }
 
+    pub fn get_hidden_rows(&self) -> i32 {
+        self.hidden_rows
+    }
+
+    pub fn is_in_bounds(&self, block: Block) -> bool {
+        block.x >= 0 && block.x < WIDTH_IN_BLOCKS &&
+        block.y >= -self.hidden_rows && block.y < HEIGHT_IN_BLOCKS
+    }
+
     pub fn set_piece(&mut self, piece: &Piece) {
         for block in piece.blocks_iter() {
-            self.set_cell_state(block, CellState::Block(piece.get_color()));
+            self.set_cell_state(block, CellState::Block(piece.get_shape()));
         }
     }
 
     pub fn is_space_occupied(&self, block: Block) -> bool {
-        match self.get_cell_state(block.x, block.y) {
-            CellState::Block(_) => true,
-            CellState::Empty => false
-        }
+        self.get_cell_state(block.x, block.y) != CellState::Empty
+    }
+
+    // Pushes a row of garbage, full apart from a hole at column `hole`, in from the bottom of the
+    // board. Everything on the board moves up a row and the top row is lost, returning whether
+    // any blocks were pushed out with it.
+    pub fn add_garbage_row(&mut self, hole: i32) -> bool {
+        let mut row = [CellState::Garbage; WIDTH_IN_BLOCKS as usize];
+        row[hole as usize] = CellState::Empty;
+        let lost = self.grid.pop_front();
+        self.grid.push_back(row);
+        lost.map_or(false, |row| !Self::row_is_empty(&row))
+    }
+
+    // How many rows still hold any garbage.
+    pub fn count_garbage_rows(&self) -> u32 {
+        self.grid.iter().filter(|row| row.contains(&CellState::Garbage)).count() as u32
     }
 
     // Removes completed rows from the board and returns the number
@@ -44,34 +65,54 @@ impl Board {
         for _ in &completed_row_indexes {
             self.grid.push_front(Self::create_empty_row());
         }
-        debug_assert!(self.grid.len() == HEIGHT_IN_BLOCKS as usize);
+        debug_assert!(self.grid.len() == (self.hidden_rows + HEIGHT_IN_BLOCKS) as usize);
         completed_row_indexes.len() as u32
     }
 
+    // Whether the row at `y` is full, waiting to be removed.
+    pub fn is_row_complete(&self, y: i32) -> bool {
+        Self::row_is_complete(&self.grid[self.row_index(y)])
+    }
+
+    pub fn has_completed_rows(&self) -> bool {
+        !self.find_completed_row_indexes().is_empty()
+    }
+
+    // Whether every cell of the board, hidden rows included, is empty.
+    pub fn is_empty(&self) -> bool {
+        self.grid.iter().all(|row| Self::row_is_empty(row))
+    }
+
     // Finds and returns the indexes of completed rows in reverse order
     fn find_completed_row_indexes(&self) -> Vec<usize> {
         let mut completed_row_indexes = Vec::new();
         let rows = self.grid.iter().rev().take_while(|&row| !Self::row_is_empty(row));
         for (i, row) in rows.enumerate() {
             if Self::row_is_complete(row) {
-                let grid_index = (HEIGHT_IN_BLOCKS - 1) as usize - i;
+                let grid_index = self.grid.len() - 1 - i;
                 completed_row_indexes.push(grid_index);
             }
         }
         completed_row_indexes
     }
 
-    fn get_cell_state(&self, x: i32, y: i32) -> CellState {
-        self.grid[y as usize][x as usize]
+    pub fn get_cell_state(&self, x: i32, y: i32) -> CellState {
+        self.grid[self.row_index(y)][x as usize]
     }
 
     fn set_cell_state(&mut self, block: Block, cell_state: CellState) {
-        self.grid[block.y as usize][block.x as usize] = cell_state;
+        let row = self.row_index(block.y);
+        self.grid[row][block.x as usize] = cell_state;
+    }
+
+    fn row_index(&self, y: i32) -> usize {
+        (y + self.hidden_rows) as usize
     }
 
-    fn create_empty_grid() -> VecDeque<GridRow> {
-        let mut grid = VecDeque::with_capacity(HEIGHT_IN_BLOCKS as usize);
-        for _ in 0..HEIGHT_IN_BLOCKS {
+    fn create_empty_grid(hidden_rows: i32) -> VecDeque<GridRow> {
+        let height = hidden_rows + HEIGHT_IN_BLOCKS;
+        let mut grid = VecDeque::with_capacity(height as usize);
+        for _ in 0..height {
             grid.push_back(Self::create_empty_row());
         }
         grid
@@ -88,94 +129,153 @@ impl Board {
     fn row_is_complete(row: &GridRow) -> bool {
         row.iter().all(|&block| block != CellState::Empty)
     }
+}
 
-    pub fn render(&self, context: Context, graphics: &mut G2d) {
-        let grid = Grid {
-            cols: WIDTH_IN_BLOCKS as u32,
-            rows: HEIGHT_IN_BLOCKS as u32,
-            units: BLOCK_SIZE
-        };
-        let line = Line::new(GREY, GRID_LINE_WIDTH);
-        let transform = context.transform.trans(GRID_X_OFFSET, GRID_Y_OFFSET);
-        grid.draw(&line, &Default::default(), transform, graphics);
-        for x in 0..WIDTH_IN_BLOCKS {
-            for y in 0..HEIGHT_IN_BLOCKS {
-                self.get_cell_state(x, y).render(x, y, context, graphics);
+#[cfg(test)]
+impl Board {
+    // Builds a board from a picture of its bottom rows, with '#' for a filled cell.
+    pub fn from_picture(hidden_rows: i32, picture: &[&str]) -> Board {
+        let mut board = Board::new(hidden_rows);
+        let top = HEIGHT_IN_BLOCKS - picture.len() as i32;
+        for (i, row) in picture.iter().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                if cell == '#' {
+                    let block = Block::new(x as i32, top + i as i32);
+                    board.set_cell_state(block, CellState::Block(Shape::Z));
+                }
             }
         }
+        board
     }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum CellState {
     Empty,
-    Block(types::Color)
-}
-impl CellState {
-    fn render(&self, x: i32, y: i32, context: Context, graphics: &mut G2d) {
-        match *self {
-            CellState::Block(color) => {
-                let rect = Rectangle {
-                    color: color,
-                    shape: rectangle::Shape::Square,
-                    border: None
-                };
-                Block::new(x, y).render_in_grid(rect, context, graphics);
-            },
-            CellState::Empty => {}
-        }
-    }
+    Block(Shape),
+    // Rows pushed onto the board rather than built from pieces
+    Garbage
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use colors::{RED, CYAN};
-    use tetromino::{Block, Piece, I};
-    use settings::*;
+    use tetromino::{Block, Piece, Shape, I};
 
     #[test]
     fn test_set_piece() {
-        let mut board = Board::new();
+        let mut board = Board::new(0);
         let piece = Piece::create(&I);
         board.set_piece(&piece);
         assert_eq!(board.get_cell_state(2, 1), CellState::Empty);
-        assert_eq!(board.get_cell_state(3, 1), CellState::Block(CYAN));
-        assert_eq!(board.get_cell_state(4, 1), CellState::Block(CYAN));
-        assert_eq!(board.get_cell_state(5, 1), CellState::Block(CYAN));
-        assert_eq!(board.get_cell_state(6, 1), CellState::Block(CYAN));
+        assert_eq!(board.get_cell_state(3, 1), CellState::Block(Shape::I));
+        assert_eq!(board.get_cell_state(4, 1), CellState::Block(Shape::I));
+        assert_eq!(board.get_cell_state(5, 1), CellState::Block(Shape::I));
+        assert_eq!(board.get_cell_state(6, 1), CellState::Block(Shape::I));
         assert_eq!(board.get_cell_state(7, 1), CellState::Empty);
     }
 
     #[test]
     fn test_is_space_occupied() {
-        let mut board = Board::new();
+        let mut board = Board::new(0);
         let block = Block{x: 2, y: 2};
-        board.set_cell_state(block, CellState::Block(RED));
+        board.set_cell_state(block, CellState::Block(Shape::Z));
         assert!(board.is_space_occupied(block));
         assert!(!board.is_space_occupied(Block{x: 0, y: 0}));
     }
 
     #[test]
     fn test_set_cell_state() {
-        let mut board = Board::new();
+        let mut board = Board::new(0);
         assert_eq!(board.get_cell_state(0, 0), CellState::Empty);
-        board.set_cell_state(Block{x: 2, y: 2}, CellState::Block(RED));
-        assert_eq!(board.get_cell_state(2, 2), CellState::Block(RED));
+        board.set_cell_state(Block{x: 2, y: 2}, CellState::Block(Shape::Z));
+        assert_eq!(board.get_cell_state(2, 2), CellState::Block(Shape::Z));
+    }
+
+    #[test]
+    fn test_hidden_rows() {
+        let mut board = Board::new(2);
+        assert_eq!(board.grid.len(), (HEIGHT_IN_BLOCKS + 2) as usize);
+        assert!(board.is_in_bounds(Block{x: 0, y: -2}));
+        assert!(!board.is_in_bounds(Block{x: 0, y: -3}));
+        assert!(board.is_in_bounds(Block{x: 0, y: HEIGHT_IN_BLOCKS - 1}));
+        assert!(!board.is_in_bounds(Block{x: 0, y: HEIGHT_IN_BLOCKS}));
+        board.set_cell_state(Block{x: 1, y: -2}, CellState::Block(Shape::Z));
+        assert_eq!(board.grid[0][1], CellState::Block(Shape::Z));
+        assert!(board.is_space_occupied(Block{x: 1, y: -2}));
+    }
+
+    #[test]
+    fn test_remove_completed_rows_with_hidden_rows() {
+        let mut board = Board::new(2);
+        let complete_row = [CellState::Block(Shape::Z); WIDTH_IN_BLOCKS as usize];
+        let bottom = board.row_index(HEIGHT_IN_BLOCKS - 1);
+        board.grid[bottom] = complete_row;
+        board.set_cell_state(Block{x: 3, y: -2}, CellState::Block(Shape::Z));
+        let n = board.remove_completed_rows();
+
+        assert_eq!(n, 1);
+        assert_eq!(board.grid.len(), (HEIGHT_IN_BLOCKS + 2) as usize);
+        assert_eq!(board.get_cell_state(3, -1), CellState::Block(Shape::Z));
+        assert_eq!(board.get_cell_state(3, -2), CellState::Empty);
+    }
+
+    #[test]
+    fn test_is_empty() {
+        let mut board = Board::new(2);
+        assert!(board.is_empty());
+        board.set_cell_state(Block{x: 0, y: -2}, CellState::Block(Shape::Z));
+        assert!(!board.is_empty());
+    }
+
+    #[test]
+    fn test_add_garbage_row() {
+        let mut board = Board::new(2);
+        board.set_cell_state(Block{x: 0, y: HEIGHT_IN_BLOCKS - 1}, CellState::Block(Shape::Z));
+        board.add_garbage_row(3);
+        board.add_garbage_row(5);
+        assert_eq!(board.grid.len(), (HEIGHT_IN_BLOCKS + 2) as usize);
+        assert_eq!(board.get_cell_state(0, HEIGHT_IN_BLOCKS - 3), CellState::Block(Shape::Z));
+        assert_eq!(board.get_cell_state(3, HEIGHT_IN_BLOCKS - 2), CellState::Empty);
+        assert_eq!(board.get_cell_state(5, HEIGHT_IN_BLOCKS - 1), CellState::Empty);
+        assert!(board.is_space_occupied(Block{x: 3, y: HEIGHT_IN_BLOCKS - 1}));
+        assert_eq!(board.count_garbage_rows(), 2);
+    }
+
+    #[test]
+    fn test_add_garbage_row_pushes_out_top() {
+        let mut board = Board::new(1);
+        board.set_cell_state(Block{x: 0, y: 0}, CellState::Block(Shape::Z));
+        assert!(!board.add_garbage_row(3));
+        assert!(board.add_garbage_row(3));
+        assert!(!board.add_garbage_row(3));
+    }
+
+    #[test]
+    fn test_completed_rows() {
+        let mut board = Board::from_picture(2, &[
+            "##########",
+            "#########."
+        ]);
+        assert!(board.has_completed_rows());
+        assert!(board.is_row_complete(HEIGHT_IN_BLOCKS - 2));
+        assert!(!board.is_row_complete(HEIGHT_IN_BLOCKS - 1));
+        board.remove_completed_rows();
+        assert!(!board.has_completed_rows());
     }
 
     #[test]
     fn test_row_is_empty() {
         let mut empty_row = [CellState::Empty; WIDTH_IN_BLOCKS as usize];
         assert!(Board::row_is_empty(&empty_row));
-        empty_row[1] = CellState::Block(RED);
+        empty_row[1] = CellState::Block(Shape::Z);
         assert!(!Board::row_is_empty(&empty_row));
     }
 
     #[test]
     fn test_row_is_complete() {
-        let mut complete_row = [CellState::Block(RED); WIDTH_IN_BLOCKS as usize];
+        let mut complete_row = [CellState::Block(Shape::Z); WIDTH_IN_BLOCKS as usize];
         assert!(Board::row_is_complete(&complete_row));
         complete_row[1] = CellState::Empty;
         assert!(!Board::row_is_complete(&complete_row));
@@ -190,8 +290,8 @@ mod tests {
     #[test]
     fn test_find_completed_row_indexes_simple() {
         let i = (HEIGHT_IN_BLOCKS - 1) as usize;
-        let mut board = Board::new();
-        let complete_row = [CellState::Block(RED); WIDTH_IN_BLOCKS as usize];
+        let mut board = Board::new(0);
+        let complete_row = [CellState::Block(Shape::Z); WIDTH_IN_BLOCKS as usize];
         board.grid[i] = complete_row;
         let result = board.find_completed_row_indexes();
         let expected = vec![i];
@@ -201,8 +301,8 @@ mod tests {
     #[test]
     fn test_find_completed_row_indexes_multiple() {
         let i = (HEIGHT_IN_BLOCKS - 1) as usize;
-        let mut board = Board::new();
-        let complete_row = [CellState::Block(RED); WIDTH_IN_BLOCKS as usize];
+        let mut board = Board::new(0);
+        let complete_row = [CellState::Block(Shape::Z); WIDTH_IN_BLOCKS as usize];
         board.grid[i] = complete_row;
         board.grid[i - 1] = complete_row;
         let result = board.find_completed_row_indexes();
@@ -213,8 +313,8 @@ mod tests {
     #[test]
     fn test_find_completed_row_indexes_skip_row() {
         let i = (HEIGHT_IN_BLOCKS - 1) as usize;
-        let mut board = Board::new();
-        let complete_row = [CellState::Block(RED); WIDTH_IN_BLOCKS as usize];
+        let mut board = Board::new(0);
+        let complete_row = [CellState::Block(Shape::Z); WIDTH_IN_BLOCKS as usize];
         let mut incomplete_row = complete_row;
         incomplete_row[4] = CellState::Empty;
         board.grid[i] = complete_row;
@@ -228,26 +328,26 @@ mod tests {
     #[test]
     fn test_remove_completed_rows_simple() {
         let i = (HEIGHT_IN_BLOCKS - 1) as usize;
-        let mut board = Board::new();
-        let complete_row = [CellState::Block(RED); WIDTH_IN_BLOCKS as usize];
+        let mut board = Board::new(0);
+        let complete_row = [CellState::Block(Shape::Z); WIDTH_IN_BLOCKS as usize];
         board.grid[i] = complete_row;
         let n = board.remove_completed_rows();
 
         assert_eq!(n, 1);
-        assert_eq!(board.grid, Board::create_empty_grid());
+        assert_eq!(board.grid, Board::create_empty_grid(0));
     }
 
     #[test]
     fn test_remove_completed_rows_moves_down() {
         let i = (HEIGHT_IN_BLOCKS - 1) as usize;
-        let mut board = Board::new();
-        let complete_row = [CellState::Block(RED); WIDTH_IN_BLOCKS as usize];
+        let mut board = Board::new(0);
+        let complete_row = [CellState::Block(Shape::Z); WIDTH_IN_BLOCKS as usize];
         board.grid[i] = complete_row;
-        board.grid[i-1][0] = CellState::Block(RED);
+        board.grid[i-1][0] = CellState::Block(Shape::Z);
         let n = board.remove_completed_rows();
 
-        let mut expected_grid = Board::create_empty_grid();
-        expected_grid[i][0] = CellState::Block(RED);
+        let mut expected_grid = Board::create_empty_grid(0);
+        expected_grid[i][0] = CellState::Block(Shape::Z);
 
         assert_eq!(n, 1);
         assert_eq!(board.grid, expected_grid);
@@ -255,17 +355,17 @@ mod tests {
     #[test]
     fn test_remove_completed_rows_moves_two_down() {
         let i = (HEIGHT_IN_BLOCKS - 1) as usize;
-        let mut board = Board::new();
-        let complete_row = [CellState::Block(RED); WIDTH_IN_BLOCKS as usize];
+        let mut board = Board::new(0);
+        let complete_row = [CellState::Block(Shape::Z); WIDTH_IN_BLOCKS as usize];
         board.grid[i] = complete_row;
-        board.grid[i-1][0] = CellState::Block(RED);
+        board.grid[i-1][0] = CellState::Block(Shape::Z);
         board.grid[i-2] = complete_row;
-        board.grid[i-3][1] = CellState::Block(RED);
+        board.grid[i-3][1] = CellState::Block(Shape::Z);
         let n = board.remove_completed_rows();
 
-        let mut expected_grid = Board::create_empty_grid();
-        expected_grid[i][0] = CellState::Block(RED);
-        expected_grid[i-1][1] = CellState::Block(RED);
+        let mut expected_grid = Board::create_empty_grid(0);
+        expected_grid[i][0] = CellState::Block(Shape::Z);
+        expected_grid[i-1][1] = CellState::Block(Shape::Z);
 
         assert_eq!(n, 2);
         assert_eq!(board.grid, expected_grid);
@@ -0,0 +1,212 @@
+use std::env;
+use std::process;
+
+use rustris::gravity::GravityCurve;
+use rustris::leveling::LevelProgression;
+use rustris::mode::{DEFAULT_DIG_ROWS, DEFAULT_MARATHON_LINES, GameMode, MarathonGoal, ZenGravity};
+use rustris::randomizer::RandomizerType;
+use rustris::scoring::ScoringType;
+use rustris::settings::{GameSettings, HEIGHT_IN_BLOCKS, MAX_GAME_LEVEL, MAX_HIDDEN_ROWS};
+use rustris::settings::MAX_PREVIEW_COUNT;
+
+
+const USAGE: &'static str = "Usage: rustris [options]
+
+Options:
+    --mode <name>           Game mode: marathon, endless, sprint, ultra, dig, survival, zen or
+                            master
+    --finish-lines <n>      Lines to clear to finish a marathon
+    --finish-level <n>      Level to clear to finish a marathon
+    --time <seconds>        How long an ultra game lasts
+    --garbage <n>           Rows of garbage to dig through in a dig game
+    --no-gravity            Play zen with pieces only falling when dropped
+    --preview <1-6>         Number of upcoming pieces shown
+    --hidden-rows <0-20>    Rows of buffer above the playfield that pieces can spawn and stack into
+    --randomizer <name>     Piece randomizer: history, 7bag, 14bag or random
+    --seed <n>              Seed for the piece randomizer
+    --das <ms>              Delay before a held sideways move starts repeating
+    --arr <ms>              Delay between repeated sideways moves, 0 moves straight to the wall
+    --sdf <factor>          How many times faster than gravity soft drop is
+    --are <ms>              Delay between a piece locking and the next one appearing
+    --line-clear-delay <ms> How long completed lines stay on the board before they are cleared
+    --partial-lock-out      End the game when any block locks above the playfield
+    --scoring <name>        Scoring system: guideline, nes or sega
+    --gravity <name>        Gravity curve: guideline or nes
+    --level <1-30>          Level to start on
+    --goal <goal>           Level progression: a number of lines per level, variable or nes";
+
+// Builds the game settings from the command line, exiting with a usage message if the arguments
+// can't be understood.
+pub fn parse_args() -> GameSettings {
+    let mut settings = GameSettings::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--mode" => {
+                settings.mode = match args.next() {
+                    Some(ref name) => parse_mode(name),
+                    None => exit_with_usage()
+                };
+            }
+            "--finish-lines" => {
+                settings.mode = match args.next().and_then(|n| n.parse().ok()) {
+                    Some(lines) if lines > 0 => GameMode::Marathon(MarathonGoal::Lines(lines)),
+                    _ => exit_with_usage()
+                };
+            }
+            "--finish-level" => {
+                settings.mode = match args.next().and_then(|n| n.parse().ok()) {
+                    Some(level) if level >= 1 && level < MAX_GAME_LEVEL => {
+                        GameMode::Marathon(MarathonGoal::Level(level))
+                    }
+                    _ => exit_with_usage()
+                };
+            }
+            "--garbage" => {
+                settings.mode = match args.next().and_then(|n| n.parse::<i32>().ok()) {
+                    Some(rows) if rows >= 1 && rows < HEIGHT_IN_BLOCKS => {
+                        GameMode::Dig(rows as u32)
+                    }
+                    _ => exit_with_usage()
+                };
+            }
+            "--no-gravity" => settings.mode = GameMode::Zen(ZenGravity::Off),
+            "--time" => {
+                settings.time_limit = match args.next().and_then(|n| n.parse::<u32>().ok()) {
+                    Some(seconds) if seconds > 0 => seconds as f64,
+                    _ => exit_with_usage()
+                };
+            }
+            "--preview" => {
+                settings.preview_count = match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) if n >= 1 && n <= MAX_PREVIEW_COUNT => n,
+                    _ => exit_with_usage()
+                };
+            }
+            "--hidden-rows" => {
+                settings.hidden_rows = match args.next().and_then(|n| n.parse().ok()) {
+                    Some(rows) if rows <= MAX_HIDDEN_ROWS => rows,
+                    _ => exit_with_usage()
+                };
+            }
+            "--randomizer" => {
+                settings.randomizer = match args.next() {
+                    Some(ref name) => parse_randomizer(name),
+                    None => exit_with_usage()
+                };
+            }
+            "--seed" => {
+                settings.seed = match args.next().and_then(|n| n.parse().ok()) {
+                    Some(seed) => Some(seed),
+                    None => exit_with_usage()
+                };
+            }
+            "--das" => settings.das = parse_millis(args.next()),
+            "--arr" => settings.arr = parse_millis(args.next()),
+            "--are" => settings.are = parse_millis(args.next()),
+            "--line-clear-delay" => settings.line_clear_delay = parse_millis(args.next()),
+            "--sdf" => {
+                settings.soft_drop_factor = match args.next().and_then(|n| n.parse::<f64>().ok()) {
+                    Some(factor) if factor >= 1.0 && factor.is_finite() => factor,
+                    _ => exit_with_usage()
+                };
+            }
+            "--partial-lock-out" => settings.partial_lock_out = true,
+            "--scoring" => {
+                settings.scoring = match args.next() {
+                    Some(ref name) => parse_scoring(name),
+                    None => exit_with_usage()
+                };
+            }
+            "--gravity" => {
+                settings.gravity = match args.next() {
+                    Some(ref name) => parse_gravity(name),
+                    None => exit_with_usage()
+                };
+            }
+            "--level" => {
+                settings.start_level = match args.next().and_then(|n| n.parse().ok()) {
+                    Some(level) if level >= 1 && level <= MAX_GAME_LEVEL => level,
+                    _ => exit_with_usage()
+                };
+            }
+            "--goal" => {
+                settings.level_progression = match args.next() {
+                    Some(ref goal) => parse_goal(goal),
+                    None => exit_with_usage()
+                };
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => exit_with_usage()
+        }
+    }
+    settings
+}
+
+fn parse_mode(name: &str) -> GameMode {
+    match name {
+        "marathon" => GameMode::Marathon(MarathonGoal::Lines(DEFAULT_MARATHON_LINES)),
+        "endless" => GameMode::Endless,
+        "sprint" => GameMode::Sprint,
+        "ultra" => GameMode::Ultra,
+        "dig" => GameMode::Dig(DEFAULT_DIG_ROWS),
+        "survival" => GameMode::Survival,
+        "zen" => GameMode::Zen(ZenGravity::Constant),
+        "master" => GameMode::Master,
+        _ => exit_with_usage()
+    }
+}
+
+fn parse_randomizer(name: &str) -> RandomizerType {
+    match name {
+        "history" => RandomizerType::History,
+        "7bag" => RandomizerType::SevenBag,
+        "14bag" => RandomizerType::FourteenBag,
+        "random" => RandomizerType::Pure,
+        _ => exit_with_usage()
+    }
+}
+
+fn parse_scoring(name: &str) -> ScoringType {
+    match name {
+        "guideline" => ScoringType::Guideline,
+        "nes" => ScoringType::Nes,
+        "sega" => ScoringType::Sega,
+        _ => exit_with_usage()
+    }
+}
+
+fn parse_gravity(name: &str) -> GravityCurve {
+    match name {
+        "guideline" => GravityCurve::Guideline,
+        "nes" => GravityCurve::Nes,
+        _ => exit_with_usage()
+    }
+}
+
+fn parse_goal(goal: &str) -> LevelProgression {
+    match goal {
+        "variable" => LevelProgression::Variable,
+        "nes" => LevelProgression::Nes,
+        _ => match goal.parse() {
+            Ok(lines) if lines > 0 => LevelProgression::Fixed(lines),
+            _ => exit_with_usage()
+        }
+    }
+}
+
+// Reads a whole number of milliseconds, returning it in seconds.
+fn parse_millis(arg: Option<String>) -> f64 {
+    match arg.and_then(|n| n.parse::<u32>().ok()) {
+        Some(millis) => millis as f64 / 1000.0,
+        None => exit_with_usage()
+    }
+}
+
+fn exit_with_usage() -> ! {
+    println!("{}", USAGE);
+    process::exit(1);
+}
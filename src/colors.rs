@@ -1,4 +1,5 @@
 use piston_window::types::Color;
+use rustris::tetromino::Shape;
 
 // Lifted and modified from Conrod
 macro_rules! make_color {
@@ -21,3 +22,18 @@ pub const RED: Color = make_color!(0xCC, 0x00, 0x00);
 
 // Grid color
 pub const GREY: Color = [0.15, 0.15, 0.15, 1.0];
+
+// Garbage color
+pub const SILVER: Color = make_color!(0x88, 0x8A, 0x85);
+
+pub fn shape_color(shape: Shape) -> Color {
+    match shape {
+        Shape::I => CYAN,
+        Shape::J => BLUE,
+        Shape::L => ORANGE,
+        Shape::O => YELLOW,
+        Shape::S => LIME,
+        Shape::T => PURPLE,
+        Shape::Z => RED
+    }
+}
@@ -0,0 +1,46 @@
+use piston_window::Key;
+
+use rustris::game::Command;
+
+
+// Turns key presses and releases into game commands. The window repeats press events while a key
+// is held, but the game does its own auto repeat, so only the first press of a key is passed on.
+pub struct Controls {
+    held_keys: Vec<Key>
+}
+impl Controls {
+    pub fn new() -> Controls {
+        Controls {
+            held_keys: Vec::new()
+        }
+    }
+
+    pub fn on_press(&mut self, key: Key) -> Option<Command> {
+        if self.held_keys.contains(&key) {
+            return None;
+        }
+        self.held_keys.push(key);
+        key_to_command(key)
+    }
+
+    pub fn on_release(&mut self, key: Key) -> Option<Command> {
+        self.held_keys.retain(|&held| held != key);
+        key_to_command(key)
+    }
+}
+
+// Maps a key to the game command it is bound to, if any.
+fn key_to_command(key: Key) -> Option<Command> {
+    match key {
+        Key::Left => Some(Command::MoveLeft),
+        Key::Right => Some(Command::MoveRight),
+        Key::Up | Key::X => Some(Command::RotateClockwise),
+        Key::Z => Some(Command::RotateCounterClockwise),
+        Key::A => Some(Command::Rotate180),
+        Key::Down => Some(Command::SoftDrop),
+        Key::Space => Some(Command::HardDrop),
+        Key::C | Key::LShift => Some(Command::Hold),
+        Key::P => Some(Command::Pause),
+        _ => None
+    }
+}
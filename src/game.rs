@@ -1,52 +1,181 @@
-use piston_window::*;
+use std::cmp::{max, min};
+use std::collections::VecDeque;
+use rand::{IsaacRng, Rng, SeedableRng, thread_rng};
 
 use board::Board;
+use master::{get_are, get_lock_delay, master_settings};
+use mode::GameMode;
 use randomizer::Randomizer;
 use tetromino::*;
 use settings::*;
-use stats::GameStats;
+use stats::{GameStats, LineClear, TSpin};
 
 
 pub struct Rustris {
+    settings: GameSettings,
+    seed: u32,
+    // Picks the holes in garbage rows, apart from the randomizer so garbage doesn't change which
+    // pieces a seed deals
+    garbage_rng: IsaacRng,
     board: Board,
-    randomizer: Randomizer,
+    randomizer: Box<Randomizer>,
     current_piece: Piece,
-    next_piece: Piece,
+    next_pieces: VecDeque<Piece>,
+    held_piece: Option<Piece>,
+    can_hold: bool,
     stats: GameStats,
     time_since_moved: f64,
+    lock_timer: f64,
+    lock_resets: u32,
+    lowest_row: i32,
+    left_held: bool,
+    right_held: bool,
+    shift_direction: Option<Direction>,
+    das_timer: f64,
+    arr_timer: f64,
+    soft_drop_held: bool,
+    last_rotation_kick: Option<usize>,
+    last_rotation_half_turn: bool,
+    last_clear: Option<LineClear>,
+    time_since_clear: f64,
+    // Seconds left before play starts, in modes that count down
+    countdown: f64,
+    // Seconds since garbage last rose, and how many rows have, in modes where it rises
+    garbage_timer: f64,
+    garbage_risen: u32,
+    // What's happening between pieces, and the seconds left before it's over
+    phase: Phase,
+    phase_timer: f64,
+    // Commands given between pieces, to be carried out once the next one appears
+    buffered_commands: Vec<Command>,
     state: GameState
 }
 impl Rustris {
     pub fn new() -> Rustris {
-        let mut randomizer = Randomizer::new();
+        Self::with_settings(GameSettings::new())
+    }
+
+    pub fn with_settings(settings: GameSettings) -> Rustris {
+        let settings = match settings.mode {
+            GameMode::Master => master_settings(settings),
+            _ => settings
+        };
+        let settings = GameSettings {
+            preview_count: max(1, min(settings.preview_count, MAX_PREVIEW_COUNT)),
+            hidden_rows: min(settings.hidden_rows, MAX_HIDDEN_ROWS),
+            // Soft drop can't be slower than gravity, and NaN is no speed at all
+            soft_drop_factor: if settings.soft_drop_factor >= 1.0 {
+                settings.soft_drop_factor
+            } else {
+                1.0
+            },
+            start_level: max(1, min(settings.start_level, MAX_GAME_LEVEL)),
+            ..settings
+        };
+        let seed = settings.seed.unwrap_or_else(|| thread_rng().gen());
+        let mut randomizer = settings.randomizer.create(seed);
         let current_piece = randomizer.create_piece();
-        let next_piece = randomizer.create_piece();
-        Rustris {
-            board: Board::new(),
+        let next_pieces = (0..settings.preview_count).map(|_| randomizer.create_piece()).collect();
+        let countdown = if settings.mode.has_countdown() { COUNTDOWN_DURATION } else { 0.0 };
+        let mut game = Rustris {
+            settings: settings,
+            seed: seed,
+            garbage_rng: IsaacRng::from_seed(&[seed, 1]),
+            board: Board::new(settings.hidden_rows as i32),
             randomizer: randomizer,
             current_piece: current_piece,
-            next_piece: next_piece,
-            stats: GameStats::new(),
+            next_pieces: next_pieces,
+            held_piece: None,
+            can_hold: true,
+            stats: GameStats::with_settings(settings),
             time_since_moved: 0.0,
-            state: GameState::Playing
+            lock_timer: 0.0,
+            lock_resets: 0,
+            lowest_row: current_piece.y,
+            left_held: false,
+            right_held: false,
+            shift_direction: None,
+            das_timer: 0.0,
+            arr_timer: 0.0,
+            soft_drop_held: false,
+            last_rotation_kick: None,
+            last_rotation_half_turn: false,
+            last_clear: None,
+            time_since_clear: 0.0,
+            countdown: countdown,
+            garbage_timer: 0.0,
+            garbage_risen: 0,
+            phase: Phase::Falling,
+            phase_timer: 0.0,
+            buffered_commands: Vec::new(),
+            state: if countdown > 0.0 { GameState::Countdown } else { GameState::Playing }
+        };
+        for _ in 0..settings.mode.get_starting_garbage() {
+            game.add_garbage_row();
         }
+        game.spawn_piece_at_top(current_piece);
+        game
     }
 
+    // Starts a new game with the same settings. Unless a seed was set the new game gets a seed of
+    // its own.
     pub fn reset(&mut self) {
-        self.board = Board::new();
-        let mut randomizer = Randomizer::new();
-        self.current_piece = randomizer.create_piece();
-        self.next_piece = randomizer.create_piece();
-        self.randomizer = randomizer;
-        self.stats = GameStats::new();
-        self.time_since_moved = 0.0;
-        self.set_game_state(GameState::Playing);
+        *self = Self::with_settings(self.settings);
+    }
+
+    pub fn get_settings(&self) -> &GameSettings {
+        &self.settings
+    }
+
+    // Picks the level the next game starts on, taking effect on reset.
+    pub fn set_start_level(&mut self, level: u32) {
+        self.settings.start_level = max(1, min(level, MAX_GAME_LEVEL));
+    }
+
+    // The seed this game's pieces are generated from.
+    pub fn get_seed(&self) -> u32 {
+        self.seed
     }
 
     pub fn get_game_stats(&self) -> &GameStats {
         &self.stats
     }
 
+    pub fn get_board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn get_current_piece(&self) -> &Piece {
+        &self.current_piece
+    }
+
+    // The upcoming pieces, soonest first.
+    pub fn get_next_pieces(&self) -> &VecDeque<Piece> {
+        &self.next_pieces
+    }
+
+    pub fn get_held_piece(&self) -> Option<&Piece> {
+        self.held_piece.as_ref()
+    }
+
+    // Holding is only allowed once per piece, until it locks.
+    pub fn can_hold(&self) -> bool {
+        self.can_hold
+    }
+
+    // The most recent line clear or T-spin, for as long as it should be announced on screen.
+    pub fn get_callout(&self) -> Option<&LineClear> {
+        if self.time_since_clear < CALLOUT_DURATION {
+            self.last_clear.as_ref()
+        } else {
+            None
+        }
+    }
+
+    pub fn get_ghost_piece(&self) -> Piece {
+        self.calculate_ghost_piece()
+    }
+
     pub fn set_current_piece(&mut self, piece: Piece) {
         self.current_piece = piece;
     }
@@ -55,12 +184,64 @@ impl Rustris {
         self.state == GameState::Playing
     }
 
+    pub fn is_counting_down(&self) -> bool {
+        self.state == GameState::Countdown
+    }
+
+    // Seconds left in the countdown before play starts.
+    pub fn get_countdown(&self) -> f64 {
+        self.countdown
+    }
+
+    // Whether the game ended by reaching its mode's goal.
+    pub fn is_finished(&self) -> bool {
+        self.state == GameState::Finished
+    }
+
+    // Whether the game ended by running out of time.
+    pub fn is_time_up(&self) -> bool {
+        self.state == GameState::TimeUp
+    }
+
+    // Seconds of play left, in modes with a time limit.
+    pub fn get_time_remaining(&self) -> Option<f64> {
+        if self.settings.mode.has_time_limit() {
+            Some((self.settings.time_limit - self.stats.get_time()).max(0.0))
+        } else {
+            None
+        }
+    }
+
+    pub fn get_lines_remaining(&self) -> Option<u32> {
+        self.settings.mode.get_lines_remaining(&self.stats)
+    }
+
+    // Garbage rows left to clear, in modes that are won by clearing garbage.
+    pub fn get_garbage_remaining(&self) -> Option<u32> {
+        self.settings.mode.get_garbage_remaining(&self.stats)
+    }
+
+    pub fn get_phase(&self) -> Phase {
+        self.phase
+    }
+
     pub fn is_paused(&self) -> bool {
         self.state == GameState::Paused
     }
 
     pub fn is_game_over(&self) -> bool {
-        self.state == GameState::GameOver
+        match self.state {
+            GameState::GameOver(_) => true,
+            _ => false
+        }
+    }
+
+    // Why the game ended, if it has.
+    pub fn get_top_out(&self) -> Option<TopOut> {
+        match self.state {
+            GameState::GameOver(top_out) => Some(top_out),
+            _ => None
+        }
     }
 
     pub fn set_game_state(&mut self, state: GameState) {
@@ -68,26 +249,298 @@ impl Rustris {
     }
 
     fn drop_delay(&self) -> f64 {
-        ((MAX_GAME_LEVEL + 1) - self.stats.get_level()) as f64 * 0.10
+        self.settings.gravity.seconds_per_row(self.gravity_level())
+    }
+
+    // Master shortens the lock delay in its last section.
+    fn lock_delay(&self) -> f64 {
+        match self.settings.mode {
+            GameMode::Master => get_lock_delay(self.stats.get_level()),
+            _ => self.settings.lock_delay
+        }
+    }
+
+    // The level whose speed pieces fall at. Modes without gravity acceleration stay at the speed
+    // they started at.
+    fn gravity_level(&self) -> u32 {
+        if self.settings.mode.has_gravity_acceleration() {
+            self.stats.get_level()
+        } else {
+            self.settings.start_level
+        }
     }
 
     fn is_valid_board_position(&self, piece: &Piece) -> bool {
         piece.blocks_iter().all(|block| {
-            (block.x >= 0 && block.x < WIDTH_IN_BLOCKS &&
-             block.y >= 0 && block.y < HEIGHT_IN_BLOCKS &&
-             !self.board.is_space_occupied(block))
+            self.board.is_in_bounds(block) && !self.board.is_space_occupied(block)
         })
     }
 
+    fn is_on_ground(&self) -> bool {
+        let moved = self.current_piece.moved(Direction::Down);
+        !self.is_valid_board_position(&moved)
+    }
+
+    // Moves the active piece, keeping track of its lock delay. Reaching a new lowest row gives the
+    // piece a fresh set of lock resets, and any other move made while on the ground uses one up
+    // to restart the lock timer.
+    fn move_current_piece(&mut self, piece: Piece) {
+        let was_on_ground = self.is_on_ground();
+        self.set_current_piece(piece);
+        self.last_rotation_kick = None;
+        if piece.y > self.lowest_row {
+            self.lowest_row = piece.y;
+            self.lock_resets = 0;
+            self.lock_timer = 0.0;
+        } else if was_on_ground && self.lock_resets < self.settings.max_lock_resets {
+            self.lock_resets += 1;
+            self.lock_timer = 0.0;
+        }
+    }
+
+    // New pieces enter just above the visible playfield, as far up as the hidden rows allow.
+    fn spawn_row(&self) -> i32 {
+        -min(self.board.get_hidden_rows(), SPAWN_ROWS_ABOVE_PLAYFIELD)
+    }
+
+    // Spawns a piece at the top of the board. Like the guideline, a piece spawned in the hidden
+    // rows drops one row straight away when there's room so it shows at the top of the playfield.
+    fn spawn_piece_at_top(&mut self, mut piece: Piece) -> bool {
+        piece.y = self.spawn_row();
+        if !self.spawn_piece(piece) {
+            return false;
+        }
+        let moved = piece.moved(Direction::Down);
+        if piece.y < 0 && self.is_valid_board_position(&moved) {
+            self.set_current_piece(moved);
+            self.lowest_row = moved.y;
+        }
+        true
+    }
+
+    // Puts a newly spawned piece into play, topping out if there is no room for it.
+    fn spawn_piece(&mut self, piece: Piece) -> bool {
+        if !self.is_valid_board_position(&piece) && self.top_out(TopOut::BlockOut) {
+            return false;
+        }
+        self.set_current_piece(piece);
+        self.lock_timer = 0.0;
+        self.lock_resets = 0;
+        self.lowest_row = piece.y;
+        self.last_rotation_kick = None;
+        true
+    }
+
     fn lock_current_piece(&mut self) {
         self.board.set_piece(&self.current_piece);
-        self.remove_completed_lines();
+        self.stats.count_piece();
+        let lock_out = self.find_lock_out();
+        let t_spin = self.detect_t_spin();
+        let delay = self.settings.line_clear_delay;
+        if lock_out.is_none() && delay > 0.0 && self.board.has_completed_rows() {
+            // The completed rows stay on show until the line clear delay is over
+            self.start_phase(Phase::LineClear(t_spin), delay);
+        } else {
+            self.finish_lock(lock_out, t_spin);
+        }
+        self.can_hold = true;
+    }
+
+    // Removes the lines a locked piece completed, then ends the game or brings in the next
+    // piece.
+    fn finish_lock(&mut self, lock_out: Option<TopOut>, t_spin: TSpin) {
+        self.remove_completed_lines(t_spin);
+        let lost = lock_out.map_or(false, |top_out| self.top_out(top_out));
+        if !lost {
+            if self.settings.mode.is_complete(&self.stats) {
+                self.state = GameState::Finished;
+            } else {
+                self.enter_next_piece();
+            }
+        }
+    }
+
+    // Brings in the next piece, once the entry delay is over if there is one.
+    fn enter_next_piece(&mut self) {
+        let delay = self.entry_delay();
+        if delay > 0.0 {
+            self.start_phase(Phase::Entry, delay);
+        } else {
+            self.spawn_next_piece();
+        }
+    }
+
+    // Puts the next piece into play, carrying out any commands given while waiting for it.
+    fn spawn_next_piece(&mut self) {
+        self.phase = Phase::Falling;
         self.get_new_piece();
+        self.replay_buffered_commands();
+    }
+
+    fn start_phase(&mut self, phase: Phase, duration: f64) {
+        self.phase = phase;
+        self.phase_timer = duration;
+    }
+
+    // Master shortens the entry delay as the game goes on.
+    fn entry_delay(&self) -> f64 {
+        match self.settings.mode {
+            GameMode::Master => get_are(self.stats.get_level()),
+            _ => self.settings.are
+        }
+    }
+
+    // Counts down the line clear or entry delay, moving on when it's over.
+    fn update_phase(&mut self, dt: f64) {
+        self.phase_timer -= dt;
+        if self.phase_timer > 0.0 {
+            return;
+        }
+        match self.phase {
+            Phase::LineClear(t_spin) => self.finish_lock(None, t_spin),
+            Phase::Entry => self.spawn_next_piece(),
+            Phase::Falling => {}
+        }
+    }
+
+    // Carries out the commands given while waiting for the piece that has just appeared, until
+    // one of them locks it.
+    fn replay_buffered_commands(&mut self) {
+        while self.phase == Phase::Falling && self.is_playing() &&
+              !self.buffered_commands.is_empty() {
+            let command = self.buffered_commands.remove(0);
+            self.perform_command(command);
+        }
+    }
+
+    // Ends the game, returning true, in modes that can be lost. Other modes clear the board and
+    // play carries on.
+    fn top_out(&mut self, top_out: TopOut) -> bool {
+        if self.settings.mode.can_top_out() {
+            self.state = GameState::GameOver(top_out);
+            true
+        } else {
+            self.board = Board::new(self.settings.hidden_rows as i32);
+            false
+        }
+    }
+
+    // A piece locking entirely above the visible playfield ends the game. With partial lock out
+    // enabled, so does a piece locking with any of its blocks up there.
+    fn find_lock_out(&self) -> Option<TopOut> {
+        let mut blocks = self.current_piece.blocks_iter();
+        if blocks.all(|block| block.y < 0) {
+            Some(TopOut::LockOut)
+        } else if self.settings.partial_lock_out &&
+                  self.current_piece.blocks_iter().any(|block| block.y < 0) {
+            Some(TopOut::PartialLockOut)
+        } else {
+            None
+        }
+    }
+
+    // Swaps the current piece with the held one. When nothing is held yet the current piece is
+    // stashed and play continues with the next piece.
+    fn hold_current_piece(&mut self) {
+        if !self.can_hold {
+            return;
+        }
+        let held = self.current_piece.respawned();
+        match self.held_piece {
+            Some(piece) => {
+                self.spawn_piece_at_top(piece);
+            }
+            None => self.get_new_piece()
+        }
+        self.held_piece = Some(held);
+        self.can_hold = false;
+        self.time_since_moved = 0.0;
+    }
+
+    // Checks whether the current piece is locking as a T-spin, using the 3-corner rule: a T whose
+    // last move was a rotation, with at least three of the four corners around its centre filled.
+    // It is a full T-spin when both corners on the pointing side are filled, or when the rotation
+    // needed the last kick of a quarter turn, and a mini otherwise.
+    fn detect_t_spin(&self) -> TSpin {
+        let kick = match self.last_rotation_kick {
+            Some(kick) if self.current_piece.get_shape() == Shape::T => kick,
+            _ => return TSpin::None
+        };
+        let (x, y) = (self.current_piece.x, self.current_piece.y);
+        let is_filled = |(corner_x, corner_y): (i32, i32)| {
+            let block = Block::new(x + corner_x, y + corner_y);
+            !self.board.is_in_bounds(block) || self.board.is_space_occupied(block)
+        };
+        let corners = [(0, 0), (2, 0), (2, 2), (0, 2)];
+        if corners.iter().filter(|&&corner| is_filled(corner)).count() < 3 {
+            return TSpin::None;
+        }
+        // The corners either side of the T's point, by rotation state
+        let rotation = self.current_piece.get_rotation();
+        let front = [corners[rotation], corners[(rotation + 1) % 4]];
+        let upgraded = kick == T_SPIN_UPGRADE_KICK && !self.last_rotation_half_turn;
+        if front.iter().all(|&corner| is_filled(corner)) || upgraded {
+            TSpin::Full
+        } else {
+            TSpin::Mini
+        }
     }
 
-    fn remove_completed_lines(&mut self) {
+    fn remove_completed_lines(&mut self, t_spin: TSpin) {
+        let garbage_rows = self.board.count_garbage_rows();
         let number_removed = self.board.remove_completed_rows();
-        self.stats.score_completed_lines(number_removed);
+        self.stats.score_completed_lines(number_removed, t_spin);
+        self.stats.count_garbage_cleared(garbage_rows - self.board.count_garbage_rows());
+        let perfect_clear = number_removed > 0 && self.board.is_empty();
+        if perfect_clear {
+            self.stats.score_perfect_clear();
+        }
+        if number_removed > 0 || t_spin != TSpin::None {
+            self.last_clear = Some(LineClear {
+                lines: number_removed,
+                t_spin: t_spin,
+                perfect_clear: perfect_clear
+            });
+            self.time_since_clear = 0.0;
+        }
+    }
+
+    // Adds a row of garbage with a random hole, returning whether it pushed blocks off the top.
+    fn add_garbage_row(&mut self) -> bool {
+        let hole = self.garbage_rng.gen_range(0, WIDTH_IN_BLOCKS);
+        self.board.add_garbage_row(hole)
+    }
+
+    // Raises a row of garbage whenever the mode's timer for it runs out.
+    fn update_rising_garbage(&mut self, dt: f64) {
+        self.garbage_timer += dt;
+        while let Some(interval) = self.settings.mode.get_garbage_interval(self.garbage_risen) {
+            if self.garbage_timer < interval || !self.is_playing() {
+                break;
+            }
+            self.garbage_timer -= interval;
+            self.raise_garbage();
+        }
+    }
+
+    // Pushes a row of garbage up under the stack. The active piece is lifted out of the way if
+    // the stack rises into it, and the game ends if there's no room left above to lift it into
+    // or the stack is pushed out of the top of the board.
+    fn raise_garbage(&mut self) {
+        self.garbage_risen += 1;
+        if self.add_garbage_row() {
+            self.state = GameState::GameOver(TopOut::GarbageOut);
+            return;
+        }
+        if self.phase == Phase::Falling && !self.is_valid_board_position(&self.current_piece) {
+            let mut lifted = self.current_piece;
+            lifted.y -= 1;
+            if self.is_valid_board_position(&lifted) {
+                self.set_current_piece(lifted);
+            } else {
+                self.state = GameState::GameOver(TopOut::GarbageOut);
+            }
+        }
     }
 
     fn calculate_ghost_piece(&self) -> Piece {
@@ -99,136 +552,357 @@ impl Rustris {
         ghost
     }
 
+    // Tries each Super Rotation System kick in order and returns the first rotation that fits,
+    // along with the index of the kick it needed.
+    fn find_kicked_rotation(&self, direction: RotationDirection) -> Option<(usize, Piece)> {
+        let rotations = self.current_piece.kicked_rotations(direction);
+        rotations.into_iter().enumerate().find(|&(_, piece)| self.is_valid_board_position(&piece))
+    }
+
+    fn rotate_current_piece(&mut self, direction: RotationDirection) {
+        if let Some((kick, piece)) = self.find_kicked_rotation(direction) {
+            self.move_current_piece(piece);
+            self.last_rotation_kick = Some(kick);
+            self.last_rotation_half_turn = direction == RotationDirection::Half;
+        }
+    }
+
     fn get_new_piece(&mut self) {
-        let next = self.next_piece;
-        if self.is_valid_board_position(&next){
-            self.set_current_piece(next);
-            self.next_piece = self.randomizer.create_piece();
-        } else {
-            self.state = GameState::GameOver;
+        let next = self.next_pieces[0];
+        if self.spawn_piece_at_top(next) {
+            self.next_pieces.pop_front();
+            self.next_pieces.push_back(self.randomizer.create_piece());
         }
     }
 
     fn update(&mut self) {
         let moved = self.current_piece.moved(Direction::Down);
-        if !self.is_valid_board_position(&moved) {
-            self.lock_current_piece();
-        } else {
-            self.set_current_piece(moved);
+        if self.is_valid_board_position(&moved) {
+            if self.soft_drop_held {
+                self.stats.score_soft_drop();
+            }
+            self.move_current_piece(moved);
         }
     }
 
-    fn handle_playing_input(&mut self, input: Input) {
-        let mut moved: Option<Piece> = None;
-        match input {
-            Input::Press(key) => {
-                match key {
-                    Button::Keyboard(Key::Up) => {
-                        moved = Some(self.current_piece.rotated());
-                    }
-                    Button::Keyboard(Key::Down) => {
-                        let move_down = self.current_piece.moved(Direction::Down);
-                        if self.is_valid_board_position(&move_down) {
-                            self.time_since_moved = 0.0;
-                            self.stats.score_soft_drop();
-                            self.set_current_piece(move_down);
-                        }
-                    }
-                    Button::Keyboard(Key::Left) => {
-                        moved = Some(self.current_piece.moved(Direction::Left));
-                    }
-                    Button::Keyboard(Key::Right) => {
-                        moved = Some(self.current_piece.moved(Direction::Right));
-                    }
-                    Button::Keyboard(Key::Space) => {
-                        let ghost = self.calculate_ghost_piece();
-                        let rows_dropped = (ghost.y - self.current_piece.y) as u32;
-                        self.stats.score_hard_drop(rows_dropped);
-                        self.set_current_piece(ghost);
-                        self.lock_current_piece();
-                    }
-                    Button::Keyboard(Key::P) => {
-                        self.state = GameState::Paused
-                    }
-                    _ => {}
-                }
-            }
+    // At 20G a piece never hangs in the air, landing as soon as there is room below it.
+    fn drop_to_ground(&mut self) {
+        let ghost = self.calculate_ghost_piece();
+        if ghost.y > self.current_piece.y {
+            self.move_current_piece(ghost);
+        }
+        self.time_since_moved = 0.0;
+    }
+
+    // A soft drop factor so large its delay rounds away to nothing lands the piece straight
+    // away, as 20G does, scoring every row it falls.
+    fn soft_drop_to_ground(&mut self) {
+        while !self.is_on_ground() {
+            self.update();
+        }
+        self.time_since_moved = 0.0;
+    }
+
+    // Moves the current piece one cell sideways, returning whether there was room to do so.
+    // Between pieces there is nothing to move.
+    fn shift_current_piece(&mut self, direction: Direction) -> bool {
+        if self.phase != Phase::Falling {
+            return false;
+        }
+        let moved = self.current_piece.moved(direction);
+        let valid = self.is_valid_board_position(&moved);
+        if valid {
+            self.move_current_piece(moved);
+        }
+        valid
+    }
+
+    // Starts shifting in `direction`: the piece moves once straight away, then again each time
+    // the auto shift timers allow while the key stays down.
+    fn start_shift(&mut self, direction: Direction) {
+        self.shift_direction = Some(direction);
+        self.das_timer = 0.0;
+        self.arr_timer = 0.0;
+        self.shift_current_piece(direction);
+    }
+
+    // Stops shifting in `direction`, falling back to the opposite direction if that key is still
+    // held.
+    fn stop_shift(&mut self, direction: Direction) {
+        if self.shift_direction != Some(direction) {
+            return;
+        }
+        self.shift_direction = None;
+        match direction {
+            Direction::Left if self.right_held => self.start_shift(Direction::Right),
+            Direction::Right if self.left_held => self.start_shift(Direction::Left),
             _ => {}
         }
-        if let Some(piece) = moved {
-            if self.is_valid_board_position(&piece) {
-                self.set_current_piece(piece);
+    }
+
+    // Delayed Auto Shift: once a direction has been held for `das` seconds the piece repeats the
+    // move every `arr` seconds, or slides straight to the wall when `arr` is zero.
+    fn update_auto_shift(&mut self, dt: f64) {
+        let direction = match self.shift_direction {
+            Some(direction) => direction,
+            None => return
+        };
+        let was_charged = self.das_timer >= self.settings.das;
+        self.das_timer += dt;
+        if self.das_timer < self.settings.das {
+            return;
+        }
+        if self.settings.arr <= 0.0 {
+            while self.shift_current_piece(direction) {}
+            return;
+        }
+        if was_charged {
+            self.arr_timer += dt;
+        } else {
+            self.arr_timer = self.das_timer - self.settings.das;
+            self.shift_current_piece(direction);
+        }
+        while self.arr_timer >= self.settings.arr {
+            self.arr_timer -= self.settings.arr;
+            self.shift_current_piece(direction);
+        }
+    }
+
+    fn gravity_delay(&self) -> f64 {
+        if self.soft_drop_held {
+            self.drop_delay() / self.settings.soft_drop_factor
+        } else {
+            self.drop_delay()
+        }
+    }
+
+    fn handle_playing_command(&mut self, command: Command) {
+        if command != Command::Pause {
+            self.stats.count_key_press();
+        }
+        if self.phase == Phase::Falling {
+            self.perform_command(command);
+        } else {
+            self.handle_waiting_command(command);
+        }
+    }
+
+    // Between pieces there's nothing to move, but sideways moves still start charging DAS and
+    // soft drop can be held ready. Rotations, holds and hard drops wait for the next piece.
+    fn handle_waiting_command(&mut self, command: Command) {
+        match command {
+            Command::MoveLeft | Command::MoveRight | Command::Pause => {
+                self.perform_command(command);
             }
+            Command::SoftDrop => self.soft_drop_held = true,
+            _ => self.buffered_commands.push(command)
         }
     }
 
-    fn handle_paused_input(&mut self, input: Input) {
-        match input {
-            Input::Press(key) => {
-                match key {
-                    Button::Keyboard(Key::P) => {
-                        self.state = GameState::Playing
-                    },
-                    _ => {}
+    fn perform_command(&mut self, command: Command) {
+        match command {
+            Command::RotateClockwise => {
+                self.rotate_current_piece(RotationDirection::Clockwise);
+            }
+            Command::RotateCounterClockwise => {
+                self.rotate_current_piece(RotationDirection::CounterClockwise);
+            }
+            Command::Rotate180 => {
+                self.rotate_current_piece(RotationDirection::Half);
+            }
+            Command::SoftDrop => {
+                self.soft_drop_held = true;
+                let move_down = self.current_piece.moved(Direction::Down);
+                if self.is_valid_board_position(&move_down) {
+                    self.time_since_moved = 0.0;
+                    self.stats.score_soft_drop();
+                    self.move_current_piece(move_down);
+                }
+            }
+            Command::MoveLeft => {
+                self.left_held = true;
+                self.start_shift(Direction::Left);
+            }
+            Command::MoveRight => {
+                self.right_held = true;
+                self.start_shift(Direction::Right);
+            }
+            Command::HardDrop => {
+                let ghost = self.calculate_ghost_piece();
+                let rows_dropped = (ghost.y - self.current_piece.y) as u32;
+                if rows_dropped > 0 {
+                    self.last_rotation_kick = None;
                 }
+                self.stats.score_hard_drop(rows_dropped);
+                self.set_current_piece(ghost);
+                self.lock_current_piece();
+            }
+            Command::Hold => {
+                self.hold_current_piece();
+            }
+            Command::Pause => {
+                self.state = GameState::Paused
+            }
+        }
+    }
+
+    fn handle_paused_command(&mut self, command: Command) {
+        match command {
+            Command::Pause => {
+                self.state = GameState::Playing
             },
             _ => {}
         }
     }
 
-    pub fn on_input(&mut self, input: Input) {
+    // Called when the input bound to `command` is pressed.
+    pub fn on_command(&mut self, command: Command) {
         match self.state {
-            GameState::Playing => self.handle_playing_input(input),
-            GameState::Paused => self.handle_paused_input(input),
+            GameState::Playing => self.handle_playing_command(command),
+            GameState::Paused => self.handle_paused_command(command),
+            _ => {}
+        }
+    }
+
+    // Called when the input bound to `command` is let go. Only movement cares about this, to
+    // stop auto shifting and soft dropping.
+    pub fn on_command_released(&mut self, command: Command) {
+        match command {
+            Command::MoveLeft => {
+                self.left_held = false;
+                self.stop_shift(Direction::Left);
+            }
+            Command::MoveRight => {
+                self.right_held = false;
+                self.stop_shift(Direction::Right);
+            }
+            Command::SoftDrop => {
+                self.soft_drop_held = false;
+            }
             _ => {}
         }
     }
 
-    pub fn on_update(&mut self, update_args: UpdateArgs) {
+    // Advances the game clock by `dt` seconds.
+    pub fn on_update(&mut self, dt: f64) {
         match self.state {
+            GameState::Countdown => {
+                self.countdown -= dt;
+                if self.countdown <= 0.0 {
+                    self.countdown = 0.0;
+                    self.state = GameState::Playing;
+                }
+            },
             GameState::Playing => {
-                self.time_since_moved += update_args.dt;
-                let delay = self.drop_delay();
-                if self.time_since_moved >= delay {
-                    self.time_since_moved -= delay;
-                    self.update();
+                if let Some(remaining) = self.get_time_remaining() {
+                    if dt >= remaining {
+                        self.stats.add_time(remaining);
+                        self.state = GameState::TimeUp;
+                        return;
+                    }
+                }
+                self.stats.add_time(dt);
+                self.update_rising_garbage(dt);
+                if !self.is_playing() {
+                    return;
+                }
+                self.time_since_clear += dt;
+                self.update_auto_shift(dt);
+                if self.phase != Phase::Falling {
+                    self.update_phase(dt);
+                    return;
+                }
+                self.time_since_moved += dt;
+                if !self.settings.mode.has_gravity() && !self.soft_drop_held {
+                    // Without gravity pieces only fall while soft dropping
+                    self.time_since_moved = 0.0;
+                } else if self.settings.gravity.is_twenty_g(self.gravity_level()) {
+                    self.drop_to_ground();
+                } else if !self.gravity_delay().is_normal() {
+                    self.soft_drop_to_ground();
+                } else {
+                    let delay = self.gravity_delay();
+                    while self.time_since_moved >= delay {
+                        self.time_since_moved -= delay;
+                        self.update();
+                    }
+                }
+                if self.is_on_ground() {
+                    self.lock_timer += dt;
+                    if self.lock_timer >= self.lock_delay() {
+                        self.lock_current_piece();
+                    }
                 }
             },
             _ => {}
         }
     }
+}
 
-    pub fn render(&mut self, context: Context, graphics: &mut G2d) {
-        self.board.render(context, graphics);
-        self.calculate_ghost_piece().render_in_grid(RenderType::Ghost, context, graphics);
-        self.current_piece.render_in_grid(RenderType::Normal, context, graphics);
-        self.next_piece.render_in_next_piece(RenderType::Normal, context, graphics);
-    }
+// The actions a front end can ask the game to perform.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateClockwise,
+    RotateCounterClockwise,
+    Rotate180,
+    Hold,
+    Pause
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum GameState {
+    // Waiting for the countdown before a race against the clock
+    Countdown,
     Playing,
     Paused,
-    GameOver
+    GameOver(TopOut),
+    // The mode's goal was reached
+    Finished,
+    // The mode's time limit ran out
+    TimeUp
 }
 
-#[derive(Clone, Copy)]
-pub struct ScreenPosition {
-    pub x: f64,
-    pub y: f64
+// What's happening in a game being played, from a piece appearing to the next one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    // A piece is in play
+    Falling,
+    // The rows a piece completed are on show before being removed, scored with this T-spin
+    LineClear(TSpin),
+    // Waiting for the next piece to appear (ARE)
+    Entry
 }
-impl ScreenPosition {
-    pub fn new(x: f64, y: f64) -> ScreenPosition {
-        ScreenPosition {x: x, y: y}
-    }
+
+// The ways a game can be lost.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TopOut {
+    // A new piece spawned overlapping the stack
+    BlockOut,
+    // A piece locked entirely above the visible playfield
+    LockOut,
+    // A piece locked partly above the visible playfield, when that rule is enabled
+    PartialLockOut,
+    // Rising garbage pushed the stack or the active piece out of the top of the board
+    GarbageOut
 }
 
 #[cfg(test)]
 mod tests {
+    use std::f64;
+
     use super::*;
-    use tetromino::{Piece, I};
-    use settings::*;
+    use board::Board;
+    use leveling::MASTER_MAX_LEVEL;
+    use master::get_are;
+    use mode::{GameMode, MarathonGoal, ZenGravity, SPRINT_LINES};
+    use mode::{SURVIVAL_ACCELERATION, SURVIVAL_START_INTERVAL};
+    use randomizer::RandomizerType;
+    use stats::{LineClear, TSpin};
+    use tetromino::{Block, Piece, RotationDirection, Shape, I, O, T};
 
     #[test]
     fn test_is_valid_board_position() {
@@ -245,9 +919,945 @@ mod tests {
         assert_eq!(game.is_valid_board_position(&p), false);
     }
 
+    #[test]
+    fn test_is_valid_board_position_hidden_rows() {
+        let mut settings = GameSettings::new();
+        settings.hidden_rows = 4;
+        let game = Rustris::with_settings(settings);
+        let mut p = Piece::create(&I);
+        p.y = -5;
+        assert!(game.is_valid_board_position(&p));
+        p.y = -6;
+        assert!(!game.is_valid_board_position(&p));
+    }
+
+    #[test]
+    fn test_spawn_row() {
+        let mut settings = GameSettings::new();
+        settings.hidden_rows = 20;
+        let game = Rustris::with_settings(settings);
+        assert_eq!(game.spawn_row(), -2);
+        assert_eq!(game.get_current_piece().y, -1);
+        settings.hidden_rows = 1;
+        assert_eq!(Rustris::with_settings(settings).get_current_piece().y, 0);
+        settings.hidden_rows = 0;
+        assert_eq!(Rustris::with_settings(settings).get_current_piece().y, 0);
+    }
+
+    #[test]
+    fn test_hidden_rows_clamped() {
+        let mut settings = GameSettings::new();
+        settings.hidden_rows = 100;
+        let game = Rustris::with_settings(settings);
+        assert_eq!(game.get_settings().hidden_rows, MAX_HIDDEN_ROWS);
+        assert_eq!(game.get_board().get_hidden_rows(), MAX_HIDDEN_ROWS as i32);
+    }
+
+    #[test]
+    fn test_rotate_kicks_off_right_wall() {
+        let mut game = Rustris::new();
+        let mut piece = Piece::create(&I).rotated(RotationDirection::Clockwise);
+        piece.x = 7;
+        piece.y = 5;
+        game.set_current_piece(piece);
+        game.on_command(Command::RotateClockwise);
+        let blocks: Vec<Block> = game.get_current_piece().blocks_iter().collect();
+        let expected = vec![Block::new(6, 7), Block::new(7, 7), Block::new(8, 7), Block::new(9, 7)];
+        assert_eq!(blocks, expected);
+    }
+
+    #[test]
+    fn test_rotate_kicks_up_off_floor() {
+        let mut game = Rustris::new();
+        let mut piece = Piece::create(&T);
+        piece.y = HEIGHT_IN_BLOCKS - 2;
+        game.set_current_piece(piece);
+        game.on_command(Command::RotateClockwise);
+        let rotated = game.get_current_piece();
+        assert_eq!(rotated.x, piece.x - 1);
+        assert_eq!(rotated.y, piece.y - 1);
+    }
+
+    #[test]
+    fn test_rotate_counter_clockwise_kicks_off_left_wall() {
+        let mut game = Rustris::new();
+        let mut piece = Piece::create(&T).rotated(RotationDirection::Clockwise);
+        piece.x = -1;
+        piece.y = 5;
+        game.set_current_piece(piece);
+        game.on_command(Command::RotateCounterClockwise);
+        let rotated = game.get_current_piece();
+        assert_eq!(rotated.x, 0);
+        assert_eq!(rotated.y, 5);
+    }
+
+    #[test]
+    fn test_rotate_blocked() {
+        let mut game = Rustris::new();
+        for y in 0..HEIGHT_IN_BLOCKS - 1 {
+            for &x in &[0, 4, 6] {
+                let mut row = Piece::create(&I);
+                row.x = x;
+                row.y = y - 1;
+                game.board.set_piece(&row);
+            }
+        }
+        let mut piece = Piece::create(&I);
+        piece.y = HEIGHT_IN_BLOCKS - 2;
+        game.set_current_piece(piece);
+        game.on_command(Command::RotateClockwise);
+        let blocks: Vec<Block> = game.get_current_piece().blocks_iter().collect();
+        let expected: Vec<Block> = piece.blocks_iter().collect();
+        assert_eq!(blocks, expected);
+    }
+
+    #[test]
+    fn test_hold_empty() {
+        let mut game = Rustris::new();
+        let current = game.current_piece;
+        let next = game.next_pieces[0];
+        game.on_command(Command::Hold);
+        assert_eq!(game.get_current_piece().get_shape(), next.get_shape());
+        assert_eq!(game.get_held_piece().unwrap().get_shape(), current.get_shape());
+        assert!(!game.can_hold());
+    }
+
+    #[test]
+    fn test_hold_swaps_and_respawns() {
+        let mut game = Rustris::new();
+        game.set_current_piece(Piece::create(&T));
+        game.on_command(Command::Hold);
+        let mut piece = Piece::create(&I);
+        piece.x = 0;
+        piece.y = 10;
+        game.set_current_piece(piece);
+        game.can_hold = true;
+        game.on_command(Command::Hold);
+        let current = game.get_current_piece();
+        assert_eq!(current.get_shape(), Shape::T);
+        assert_eq!((current.x, current.y), (Piece::create(&T).x, game.spawn_row() + 1));
+        let held = game.get_held_piece().unwrap();
+        assert_eq!(held.get_shape(), Shape::I);
+        assert_eq!((held.x, held.y), (Piece::create(&I).x, Piece::create(&I).y));
+    }
+
+    #[test]
+    fn test_hold_once_per_piece() {
+        let mut game = Rustris::new();
+        game.on_command(Command::Hold);
+        let current = game.current_piece;
+        let held = game.held_piece.unwrap();
+        game.on_command(Command::Hold);
+        assert_eq!(game.get_current_piece().get_shape(), current.get_shape());
+        assert_eq!(game.get_held_piece().unwrap().get_shape(), held.get_shape());
+
+        game.on_command(Command::HardDrop);
+        assert!(game.can_hold());
+    }
+
+    #[test]
+    fn test_next_pieces() {
+        let mut settings = GameSettings::new();
+        settings.preview_count = 3;
+        let mut game = Rustris::with_settings(settings);
+        assert_eq!(game.get_next_pieces().len(), 3);
+        let upcoming: Vec<Shape> = game.next_pieces.iter().map(|p| p.get_shape()).collect();
+        game.on_command(Command::HardDrop);
+        assert_eq!(game.get_current_piece().get_shape(), upcoming[0]);
+        assert_eq!(game.get_next_pieces().len(), 3);
+        assert_eq!(game.next_pieces[0].get_shape(), upcoming[1]);
+        assert_eq!(game.next_pieces[1].get_shape(), upcoming[2]);
+    }
+
+    #[test]
+    fn test_preview_count_clamped() {
+        let mut settings = GameSettings::new();
+        settings.preview_count = 0;
+        assert_eq!(Rustris::with_settings(settings).get_next_pieces().len(), 1);
+        settings.preview_count = MAX_PREVIEW_COUNT + 4;
+        let game = Rustris::with_settings(settings);
+        assert_eq!(game.get_next_pieces().len(), MAX_PREVIEW_COUNT);
+        assert_eq!(game.get_settings().preview_count, MAX_PREVIEW_COUNT);
+    }
+
+    #[test]
+    fn test_seeded_games_match() {
+        let mut settings = GameSettings::new();
+        settings.randomizer = RandomizerType::SevenBag;
+        settings.seed = Some(42);
+        let mut first = Rustris::with_settings(settings);
+        let mut second = Rustris::with_settings(settings);
+        assert_eq!(first.get_seed(), 42);
+        for _ in 0..10 {
+            assert_eq!(first.get_current_piece().get_shape(),
+                       second.get_current_piece().get_shape());
+            first.on_command(Command::HardDrop);
+            second.on_command(Command::HardDrop);
+        }
+    }
+
+    #[test]
+    fn test_reset_keeps_seed() {
+        let mut settings = GameSettings::new();
+        settings.seed = Some(7);
+        let mut game = Rustris::with_settings(settings);
+        let shapes: Vec<Shape> = game.next_pieces.iter().map(|p| p.get_shape()).collect();
+        game.on_command(Command::HardDrop);
+        game.reset();
+        assert_eq!(game.get_seed(), 7);
+        let reset_shapes: Vec<Shape> = game.next_pieces.iter().map(|p| p.get_shape()).collect();
+        assert_eq!(shapes, reset_shapes);
+    }
+
+    fn grounded_game() -> Rustris {
+        let mut settings = GameSettings::new();
+        settings.lock_delay = 0.5;
+        settings.max_lock_resets = 2;
+        let mut game = Rustris::with_settings(settings);
+        let mut piece = Piece::create(&T);
+        piece.y = HEIGHT_IN_BLOCKS - 2;
+        game.spawn_piece(piece);
+        game
+    }
+
+    fn lock_count(game: &Rustris) -> usize {
+        (0..WIDTH_IN_BLOCKS).filter(|&x| {
+            game.board.is_space_occupied(Block::new(x, HEIGHT_IN_BLOCKS - 1))
+        }).count()
+    }
+
+    #[test]
+    fn test_lock_delay() {
+        let mut game = grounded_game();
+        game.on_update(0.3);
+        assert_eq!(lock_count(&game), 0);
+        game.on_update(0.3);
+        assert_eq!(lock_count(&game), 3);
+    }
+
+    #[test]
+    fn test_lock_delay_reset_by_move() {
+        let mut game = grounded_game();
+        game.on_update(0.4);
+        game.on_command(Command::MoveLeft);
+        game.on_update(0.4);
+        assert_eq!(lock_count(&game), 0);
+        game.on_update(0.2);
+        assert_eq!(lock_count(&game), 3);
+    }
+
+    #[test]
+    fn test_lock_delay_reset_limit() {
+        let mut game = grounded_game();
+        game.on_update(0.4);
+        game.on_command(Command::MoveLeft);
+        game.on_update(0.4);
+        game.on_command(Command::MoveRight);
+        game.on_update(0.4);
+        game.on_command(Command::MoveLeft);
+        game.on_update(0.1);
+        assert_eq!(lock_count(&game), 3);
+    }
+
+    #[test]
+    fn test_lock_resets_restored_on_lower_row() {
+        let mut game = grounded_game();
+        let mut ledge = Piece::create(&I);
+        ledge.x = 0;
+        ledge.y = HEIGHT_IN_BLOCKS - 2;
+        game.board.set_piece(&ledge);
+        let mut piece = Piece::create(&T);
+        piece.x = 0;
+        piece.y = HEIGHT_IN_BLOCKS - 3;
+        game.spawn_piece(piece);
+        game.on_command(Command::MoveRight);
+        game.on_command(Command::MoveRight);
+        assert_eq!(game.lock_resets, 2);
+        game.on_command(Command::MoveRight);
+        game.on_command(Command::MoveRight);
+        assert_eq!(game.lock_resets, 2);
+        game.on_update(0.1);
+        game.on_command(Command::SoftDrop);
+        assert_eq!(game.lock_resets, 0);
+        assert_eq!(game.lock_timer, 0.0);
+    }
+
+    #[test]
+    fn test_hard_drop_locks_immediately() {
+        let mut game = Rustris::new();
+        game.set_current_piece(Piece::create(&T));
+        game.on_command(Command::HardDrop);
+        assert_eq!(lock_count(&game), 3);
+    }
+
+    fn shifting_game(arr: f64) -> Rustris {
+        let mut settings = GameSettings::new();
+        settings.das = 0.2;
+        settings.arr = arr;
+        let mut game = Rustris::with_settings(settings);
+        game.set_current_piece(Piece::create(&T));
+        game
+    }
+
+    #[test]
+    fn test_shift_moves_once_on_press() {
+        let mut game = shifting_game(0.05);
+        let x = game.current_piece.x;
+        game.on_command(Command::MoveLeft);
+        assert_eq!(game.current_piece.x, x - 1);
+        game.on_update(0.1);
+        assert_eq!(game.current_piece.x, x - 1);
+    }
+
+    #[test]
+    fn test_auto_shift_repeats_after_das() {
+        let mut game = shifting_game(0.05);
+        let x = game.current_piece.x;
+        game.on_command(Command::MoveRight);
+        game.on_update(0.2);
+        assert_eq!(game.current_piece.x, x + 2);
+        game.on_update(0.1);
+        assert_eq!(game.current_piece.x, x + 4);
+        game.on_command_released(Command::MoveRight);
+        game.on_update(0.1);
+        assert_eq!(game.current_piece.x, x + 4);
+    }
+
+    #[test]
+    fn test_auto_shift_zero_arr_slides_to_wall() {
+        let mut game = shifting_game(0.0);
+        game.on_command(Command::MoveLeft);
+        game.on_update(0.2);
+        assert_eq!(game.current_piece.x, 0);
+    }
+
+    #[test]
+    fn test_auto_shift_falls_back_to_held_direction() {
+        let mut game = shifting_game(0.05);
+        let x = game.current_piece.x;
+        game.on_command(Command::MoveLeft);
+        game.on_command(Command::MoveRight);
+        assert_eq!(game.current_piece.x, x);
+        game.on_command_released(Command::MoveRight);
+        assert_eq!(game.current_piece.x, x - 1);
+        game.on_command_released(Command::MoveLeft);
+        assert_eq!(game.shift_direction, None);
+    }
+
+    #[test]
+    fn test_held_soft_drop() {
+        let mut settings = GameSettings::new();
+        settings.soft_drop_factor = 20.0;
+        let mut game = Rustris::with_settings(settings);
+        game.set_current_piece(Piece::create(&T));
+        let y = game.current_piece.y;
+        game.on_command(Command::SoftDrop);
+        assert_eq!(game.current_piece.y, y + 1);
+        game.on_update(0.2);
+        assert_eq!(game.current_piece.y, y + 5);
+        assert_eq!(game.get_game_stats().get_score(), 5);
+        game.on_command_released(Command::SoftDrop);
+        game.on_update(0.2);
+        assert_eq!(game.current_piece.y, y + 5);
+    }
+
+    #[test]
+    fn test_unbounded_soft_drop() {
+        for &factor in &[1e308, f64::INFINITY] {
+            let mut settings = GameSettings::new();
+            settings.soft_drop_factor = factor;
+            let mut game = Rustris::with_settings(settings);
+            game.set_current_piece(Piece::create(&T));
+            let ghost = game.calculate_ghost_piece();
+            game.on_command(Command::SoftDrop);
+            game.on_update(1.0 / 60.0);
+            assert_eq!(game.current_piece.y, ghost.y);
+            assert_eq!(game.get_game_stats().get_score(), ghost.y as u32);
+        }
+    }
+
+    #[test]
+    fn test_soft_drop_factor_normalized() {
+        for &factor in &[0.0, -20.0, f64::NAN] {
+            let mut settings = GameSettings::new();
+            settings.soft_drop_factor = factor;
+            let game = Rustris::with_settings(settings);
+            assert_eq!(game.get_settings().soft_drop_factor, 1.0);
+        }
+    }
+
+    #[test]
+    fn test_block_out() {
+        let mut game = Rustris::new();
+        let mut piece = Piece::create(&T);
+        piece.x = 0;
+        piece.y = HEIGHT_IN_BLOCKS - 2;
+        game.set_current_piece(piece);
+        for &y in &[-3, -2] {
+            let mut row = Piece::create(&I);
+            row.y = y;
+            game.board.set_piece(&row);
+        }
+        game.on_command(Command::HardDrop);
+        assert!(game.is_game_over());
+        assert_eq!(game.get_top_out(), Some(TopOut::BlockOut));
+    }
+
+    #[test]
+    fn test_lock_out() {
+        let mut game = Rustris::new();
+        let mut piece = Piece::create(&O);
+        piece.y = -3;
+        game.spawn_piece(piece);
+        let mut stack = Piece::create(&O);
+        stack.y = -1;
+        game.board.set_piece(&stack);
+        game.on_command(Command::HardDrop);
+        assert_eq!(game.get_top_out(), Some(TopOut::LockOut));
+    }
+
+    #[test]
+    fn test_partial_lock_out() {
+        let mut settings = GameSettings::new();
+        settings.partial_lock_out = true;
+        let mut game = Rustris::with_settings(settings);
+        let mut stack = Piece::create(&O);
+        stack.x = 0;
+        stack.y = 1;
+        game.board.set_piece(&stack);
+        let mut piece = Piece::create(&T);
+        piece.x = 0;
+        piece.y = -2;
+        game.spawn_piece(piece);
+        game.on_command(Command::HardDrop);
+        assert_eq!(game.get_top_out(), Some(TopOut::PartialLockOut));
+    }
+
+    #[test]
+    fn test_partial_lock_out_disabled() {
+        let mut game = Rustris::new();
+        let mut stack = Piece::create(&O);
+        stack.x = 0;
+        stack.y = 1;
+        game.board.set_piece(&stack);
+        let mut piece = Piece::create(&T);
+        piece.x = 0;
+        piece.y = -2;
+        game.spawn_piece(piece);
+        game.on_command(Command::HardDrop);
+        assert!(!game.is_game_over());
+    }
+
+    #[test]
+    fn test_t_spin_double() {
+        let mut game = Rustris::new();
+        game.board = Board::from_picture(DEFAULT_HIDDEN_ROWS as i32, &[
+            "...#......",
+            "###...####",
+            "####.#####"
+        ]);
+        let mut piece = Piece::create(&T).rotated(RotationDirection::Clockwise);
+        piece.y = HEIGHT_IN_BLOCKS - 3;
+        game.set_current_piece(piece);
+        game.on_command(Command::RotateClockwise);
+        game.on_command(Command::HardDrop);
+        let expected = LineClear { lines: 2, t_spin: TSpin::Full, perfect_clear: false };
+        assert_eq!(game.get_callout(), Some(&expected));
+        assert_eq!(game.get_game_stats().get_score(), 1200);
+    }
+
+    #[test]
+    fn test_t_spin_needs_rotation() {
+        let mut game = Rustris::new();
+        game.board = Board::from_picture(DEFAULT_HIDDEN_ROWS as i32, &[
+            "###...####",
+            "####.#####"
+        ]);
+        let mut piece = Piece::create(&T).rotated(RotationDirection::Half);
+        piece.y = HEIGHT_IN_BLOCKS - 4;
+        game.set_current_piece(piece);
+        game.on_command(Command::HardDrop);
+        let expected = LineClear { lines: 2, t_spin: TSpin::None, perfect_clear: true };
+        assert_eq!(game.get_callout(), Some(&expected));
+    }
+
+    #[test]
+    fn test_t_spin_mini() {
+        let mut game = Rustris::new();
+        game.board = Board::from_picture(DEFAULT_HIDDEN_ROWS as i32, &[".#........"]);
+        let mut piece = Piece::create(&T).rotated(RotationDirection::Clockwise);
+        piece.x = -1;
+        piece.y = HEIGHT_IN_BLOCKS - 3;
+        game.set_current_piece(piece);
+        game.last_rotation_kick = Some(1);
+        assert_eq!(game.detect_t_spin(), TSpin::Mini);
+        game.last_rotation_kick = Some(T_SPIN_UPGRADE_KICK);
+        assert_eq!(game.detect_t_spin(), TSpin::Full);
+        game.last_rotation_kick = None;
+        assert_eq!(game.detect_t_spin(), TSpin::None);
+    }
+
+    #[test]
+    fn test_t_spin_half_turn_not_upgraded() {
+        let mut game = Rustris::new();
+        game.board = Board::from_picture(DEFAULT_HIDDEN_ROWS as i32, &[
+            "#.#.......",
+            "#.........",
+            "#.........",
+            "..#.......",
+            ".........."
+        ]);
+        let mut piece = Piece::create(&T).rotated(RotationDirection::CounterClockwise);
+        piece.x = 0;
+        piece.y = HEIGHT_IN_BLOCKS - 3;
+        game.set_current_piece(piece);
+        game.on_command(Command::Rotate180);
+        assert_eq!(game.last_rotation_kick, Some(T_SPIN_UPGRADE_KICK));
+        assert_eq!(game.detect_t_spin(), TSpin::Mini);
+    }
+
+    #[test]
+    fn test_perfect_clear() {
+        let mut game = Rustris::new();
+        game.board = Board::from_picture(DEFAULT_HIDDEN_ROWS as i32, &[
+            "###....###",
+            "##########"
+        ]);
+        let mut piece = Piece::create(&I);
+        piece.y = HEIGHT_IN_BLOCKS - 3;
+        game.set_current_piece(piece);
+        game.on_command(Command::HardDrop);
+        let expected = LineClear { lines: 2, t_spin: TSpin::None, perfect_clear: true };
+        assert_eq!(game.get_callout(), Some(&expected));
+        assert!(game.get_board().is_empty());
+        assert_eq!(game.get_game_stats().get_perfect_clears(), 1);
+        assert_eq!(game.get_game_stats().get_score(), 300 + 1200);
+    }
+
+    #[test]
+    fn test_callout_expires() {
+        let mut game = Rustris::new();
+        game.last_clear = Some(LineClear { lines: 4, t_spin: TSpin::None, perfect_clear: false });
+        assert!(game.get_callout().is_some());
+        game.on_update(CALLOUT_DURATION);
+        assert!(game.get_callout().is_none());
+    }
+
+    fn game_with_mode(mode: GameMode) -> Rustris {
+        Rustris::with_settings(GameSettings { mode: mode, ..GameSettings::new() })
+    }
+
+    // Leaves the game an I piece's hard drop away from clearing a line.
+    fn one_line_from_clear(game: &mut Rustris) {
+        game.board = Board::from_picture(DEFAULT_HIDDEN_ROWS as i32, &["###....###"]);
+        let mut piece = Piece::create(&I);
+        piece.y = HEIGHT_IN_BLOCKS - 2;
+        game.set_current_piece(piece);
+    }
+
+    #[test]
+    fn test_sprint_countdown() {
+        let mut game = game_with_mode(GameMode::Sprint);
+        assert!(game.is_counting_down());
+        game.on_command(Command::HardDrop);
+        assert_eq!(game.get_game_stats().get_pieces(), 0);
+        game.on_update(COUNTDOWN_DURATION - 1.0);
+        assert_eq!(game.get_countdown(), 1.0);
+        assert_eq!(game.get_game_stats().get_time(), 0.0);
+        game.on_update(1.0);
+        assert!(game.is_playing());
+        game.on_update(0.25);
+        assert_eq!(game.get_game_stats().get_time(), 0.25);
+    }
+
+    #[test]
+    fn test_sprint_finishes() {
+        let mut game = game_with_mode(GameMode::Sprint);
+        game.on_update(COUNTDOWN_DURATION);
+        game.stats.set_lines(SPRINT_LINES - 1);
+        one_line_from_clear(&mut game);
+        assert_eq!(game.get_lines_remaining(), Some(1));
+        game.on_command(Command::HardDrop);
+        assert!(game.is_finished());
+        assert_eq!(game.get_lines_remaining(), Some(0));
+        assert_eq!(game.get_game_stats().get_pieces(), 1);
+        assert_eq!(game.get_game_stats().get_key_presses(), 1);
+    }
+
+    #[test]
+    fn test_ultra_time_up() {
+        let settings = GameSettings {
+            mode: GameMode::Ultra,
+            time_limit: 10.0,
+            ..GameSettings::new()
+        };
+        let mut game = Rustris::with_settings(settings);
+        assert!(game.is_counting_down());
+        assert_eq!(game.get_time_remaining(), Some(10.0));
+        game.on_update(COUNTDOWN_DURATION);
+        game.on_update(4.0);
+        assert_eq!(game.get_time_remaining(), Some(6.0));
+        game.on_update(7.0);
+        assert!(game.is_time_up());
+        assert!(!game.is_game_over());
+        assert_eq!(game.get_time_remaining(), Some(0.0));
+        assert_eq!(game.get_game_stats().get_time(), 10.0);
+        game.on_command(Command::HardDrop);
+        assert_eq!(game.get_game_stats().get_pieces(), 0);
+    }
+
+    #[test]
+    fn test_ultra_default_time_limit() {
+        let game = game_with_mode(GameMode::Ultra);
+        assert_eq!(game.get_time_remaining(), Some(DEFAULT_TIME_LIMIT));
+    }
+
+    fn marathon_game(goal: MarathonGoal) -> Rustris {
+        let mut game = game_with_mode(GameMode::Marathon(goal));
+        one_line_from_clear(&mut game);
+        game
+    }
+
+    #[test]
+    fn test_marathon_line_goal() {
+        let mut game = marathon_game(MarathonGoal::Lines(150));
+        assert!(game.is_playing());
+        game.stats.set_lines(148);
+        assert_eq!(game.get_lines_remaining(), Some(2));
+        game.on_command(Command::HardDrop);
+        assert!(game.is_playing());
+
+        let mut game = marathon_game(MarathonGoal::Lines(150));
+        game.stats.set_lines(149);
+        game.on_command(Command::HardDrop);
+        assert!(game.is_finished());
+    }
+
+    #[test]
+    fn test_marathon_level_goal() {
+        let mut game = marathon_game(MarathonGoal::Level(15));
+        game.stats.set_level(15);
+        assert_eq!(game.get_lines_remaining(), None);
+        game.on_command(Command::HardDrop);
+        assert!(game.is_playing());
+
+        let mut game = marathon_game(MarathonGoal::Level(15));
+        game.stats.set_level(16);
+        game.on_command(Command::HardDrop);
+        assert!(game.is_finished());
+    }
+
+    #[test]
+    fn test_dig() {
+        let mut game = game_with_mode(GameMode::Dig(3));
+        assert!(game.is_counting_down());
+        assert_eq!(game.get_board().count_garbage_rows(), 3);
+        assert_eq!(game.get_garbage_remaining(), Some(3));
+        game.on_update(COUNTDOWN_DURATION);
+
+        // Swap in garbage with known holes, the top two lined up under an I
+        game.board = Board::new(DEFAULT_HIDDEN_ROWS as i32);
+        game.board.add_garbage_row(4);
+        game.board.add_garbage_row(4);
+        game.board.add_garbage_row(0);
+        let mut piece = Piece::create(&I).rotated(RotationDirection::Clockwise);
+        piece.x = 2;
+        game.set_current_piece(piece);
+        game.on_command(Command::HardDrop);
+        assert_eq!(game.get_game_stats().get_garbage_cleared(), 2);
+        assert_eq!(game.get_garbage_remaining(), Some(1));
+        assert!(game.is_playing());
+    }
+
+    #[test]
+    fn test_garbage_holes_follow_seed() {
+        let settings = GameSettings {
+            mode: GameMode::Dig(10),
+            seed: Some(42),
+            ..GameSettings::new()
+        };
+        let first = Rustris::with_settings(settings);
+        let second = Rustris::with_settings(settings);
+        for y in 0..HEIGHT_IN_BLOCKS {
+            for x in 0..WIDTH_IN_BLOCKS {
+                assert_eq!(first.get_board().get_cell_state(x, y),
+                           second.get_board().get_cell_state(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn test_survival_garbage_rises_faster() {
+        let mut game = game_with_mode(GameMode::Survival);
+        assert!(game.is_playing());
+        game.on_update(SURVIVAL_START_INTERVAL - 0.5);
+        assert_eq!(game.get_board().count_garbage_rows(), 0);
+        game.on_update(0.5);
+        assert_eq!(game.get_board().count_garbage_rows(), 1);
+        game.on_update(SURVIVAL_START_INTERVAL * SURVIVAL_ACCELERATION);
+        assert_eq!(game.get_board().count_garbage_rows(), 2);
+        assert!(game.is_playing());
+    }
+
+    #[test]
+    fn test_rising_garbage_lifts_piece() {
+        let mut game = game_with_mode(GameMode::Survival);
+        let mut piece = Piece::create(&I);
+        piece.y = HEIGHT_IN_BLOCKS - 2;
+        game.set_current_piece(piece);
+        game.raise_garbage();
+        assert!(game.is_playing());
+        assert_eq!(game.get_current_piece().y, HEIGHT_IN_BLOCKS - 3);
+    }
+
+    #[test]
+    fn test_rising_garbage_tops_out() {
+        let top = -(DEFAULT_HIDDEN_ROWS as i32);
+        let mut stacked = Piece::create(&I);
+        stacked.y = top - 1;
+        let mut game = game_with_mode(GameMode::Survival);
+        game.board.set_piece(&stacked);
+        game.raise_garbage();
+        assert_eq!(game.get_top_out(), Some(TopOut::GarbageOut));
+
+        // A piece at the very top has nowhere to be lifted to
+        let mut game = game_with_mode(GameMode::Survival);
+        game.set_current_piece(stacked);
+        stacked.y += 1;
+        game.board.set_piece(&stacked);
+        game.raise_garbage();
+        assert_eq!(game.get_top_out(), Some(TopOut::GarbageOut));
+    }
+
+    #[test]
+    fn test_zen_block_out_clears_board() {
+        let mut game = game_with_mode(GameMode::Zen(ZenGravity::Constant));
+        let mut piece = Piece::create(&T);
+        piece.x = 0;
+        piece.y = HEIGHT_IN_BLOCKS - 2;
+        game.set_current_piece(piece);
+        for &y in &[-3, -2] {
+            let mut row = Piece::create(&I);
+            row.y = y;
+            game.board.set_piece(&row);
+        }
+        game.on_command(Command::HardDrop);
+        assert!(game.is_playing());
+        assert!(game.get_board().is_empty());
+        assert_eq!(game.get_game_stats().get_pieces(), 1);
+    }
+
+    #[test]
+    fn test_zen_lock_out_clears_board() {
+        let mut game = game_with_mode(GameMode::Zen(ZenGravity::Constant));
+        let mut piece = Piece::create(&O);
+        piece.y = -3;
+        game.spawn_piece(piece);
+        let mut stack = Piece::create(&O);
+        stack.y = -1;
+        game.board.set_piece(&stack);
+        game.on_command(Command::HardDrop);
+        assert!(game.is_playing());
+        assert!(game.get_board().is_empty());
+    }
+
+    #[test]
+    fn test_zen_gravity_stays_constant() {
+        let mut game = game_with_mode(GameMode::Zen(ZenGravity::Constant));
+        game.stats.set_level(10);
+        assert!((game.drop_delay() - 1.0).abs() < 1e-9);
+        game.stats.set_level(MAX_GAME_LEVEL);
+        game.on_update(1.0 / 60.0);
+        assert!(!game.is_on_ground());
+    }
+
+    #[test]
+    fn test_zen_gravity_off() {
+        let mut game = game_with_mode(GameMode::Zen(ZenGravity::Off));
+        let y = game.get_current_piece().y;
+        game.on_update(5.0);
+        assert_eq!(game.get_current_piece().y, y);
+        game.on_command(Command::SoftDrop);
+        assert_eq!(game.get_current_piece().y, y + 1);
+        game.on_update(game.drop_delay() / game.settings.soft_drop_factor);
+        assert_eq!(game.get_current_piece().y, y + 2);
+        game.on_command_released(Command::SoftDrop);
+        game.on_update(5.0);
+        assert_eq!(game.get_current_piece().y, y + 2);
+    }
+
+    fn master_game() -> Rustris {
+        let mut game = game_with_mode(GameMode::Master);
+        game.on_update(COUNTDOWN_DURATION);
+        game
+    }
+
+    #[test]
+    fn test_master() {
+        let settings = GameSettings {
+            mode: GameMode::Master,
+            randomizer: RandomizerType::SevenBag,
+            ..GameSettings::new()
+        };
+        let game = Rustris::with_settings(settings);
+        assert_eq!(game.get_settings().randomizer, RandomizerType::History);
+
+        let mut game = master_game();
+        assert_eq!(game.get_game_stats().get_level(), 0);
+        game.on_command(Command::HardDrop);
+        assert_eq!(game.get_game_stats().get_level(), 1);
+        assert_eq!(game.get_phase(), Phase::Entry);
+        assert!((game.lock_delay() - 0.5).abs() < 1e-9);
+        game.stats.set_level(900);
+        assert!(game.lock_delay() < 0.5);
+        game.on_update(get_are(0));
+        assert_eq!(game.get_phase(), Phase::Falling);
+        game.on_update(1.0 / 60.0);
+        assert!(game.is_on_ground());
+    }
+
+    #[test]
+    fn test_master_entry_delay_shortens() {
+        let mut game = master_game();
+        game.stats.set_level(700);
+        game.on_command(Command::HardDrop);
+        assert_eq!(game.get_phase(), Phase::Entry);
+        assert!(get_are(700) < get_are(0));
+        game.on_update(get_are(700));
+        assert_eq!(game.get_phase(), Phase::Falling);
+    }
+
+    #[test]
+    fn test_master_finishes() {
+        let mut game = master_game();
+        game.stats.set_level(MASTER_MAX_LEVEL - 1);
+        one_line_from_clear(&mut game);
+        game.on_command(Command::HardDrop);
+        assert!(game.is_finished());
+        assert_eq!(game.get_game_stats().get_level(), MASTER_MAX_LEVEL);
+    }
+
+    fn delayed_game(are: f64, line_clear_delay: f64) -> Rustris {
+        let settings = GameSettings {
+            are: are,
+            line_clear_delay: line_clear_delay,
+            ..GameSettings::new()
+        };
+        let mut game = Rustris::with_settings(settings);
+        one_line_from_clear(&mut game);
+        game
+    }
+
+    #[test]
+    fn test_line_clear_delay() {
+        let mut game = delayed_game(0.0, 0.25);
+        let next = game.get_next_pieces()[0];
+        game.on_command(Command::HardDrop);
+        assert_eq!(game.get_phase(), Phase::LineClear(TSpin::None));
+        assert!(game.get_board().is_row_complete(HEIGHT_IN_BLOCKS - 1));
+        assert_eq!(game.get_game_stats().get_lines(), 0);
+        game.on_update(0.125);
+        assert_eq!(game.get_phase(), Phase::LineClear(TSpin::None));
+        game.on_update(0.125);
+        assert_eq!(game.get_phase(), Phase::Falling);
+        assert!(game.get_board().is_empty());
+        assert_eq!(game.get_game_stats().get_lines(), 1);
+        assert_eq!(game.get_current_piece().get_shape(), next.get_shape());
+    }
+
+    #[test]
+    fn test_are() {
+        let mut game = delayed_game(0.25, 0.0);
+        let next = game.get_next_pieces()[0];
+        game.on_command(Command::HardDrop);
+        assert_eq!(game.get_phase(), Phase::Entry);
+        assert_eq!(game.get_game_stats().get_lines(), 1);
+        // The locked piece is still the current one until the next appears
+        assert_eq!(game.get_current_piece().y, HEIGHT_IN_BLOCKS - 2);
+        game.on_update(0.25);
+        assert_eq!(game.get_phase(), Phase::Falling);
+        assert_eq!(game.get_current_piece().get_shape(), next.get_shape());
+    }
+
+    #[test]
+    fn test_input_buffered_between_pieces() {
+        let mut game = delayed_game(0.25, 0.25);
+        game.on_command(Command::HardDrop);
+        game.on_command(Command::RotateClockwise);
+        game.on_update(0.25);
+        game.on_command(Command::HardDrop);
+        assert_eq!(game.get_phase(), Phase::Entry);
+        assert_eq!(game.get_game_stats().get_pieces(), 1);
+        game.on_update(0.25);
+        assert_eq!(game.get_current_piece().get_rotation(), 1);
+        assert_eq!(game.get_phase(), Phase::Entry);
+        assert_eq!(game.get_game_stats().get_pieces(), 2);
+        assert_eq!(game.get_game_stats().get_key_presses(), 3);
+    }
+
+    #[test]
+    fn test_das_charges_between_pieces() {
+        let mut game = delayed_game(0.5, 0.0);
+        game.next_pieces[0] = Piece::create(&T);
+        let left_column = |game: &Rustris| {
+            game.get_current_piece().blocks_iter().map(|block| block.x).min()
+        };
+        game.on_command(Command::HardDrop);
+        game.on_command(Command::MoveLeft);
+        game.on_update(0.5);
+        assert_eq!(game.get_phase(), Phase::Falling);
+        assert_eq!(left_column(&game), Some(3));
+        // Already charged, so the piece repeats its move at the auto repeat rate straight away
+        game.on_update(DEFAULT_ARR);
+        assert_eq!(left_column(&game), Some(2));
+        game.on_update(2.0 * DEFAULT_ARR);
+        assert_eq!(left_column(&game), Some(0));
+    }
+
+    #[test]
+    fn test_pause_keeps_phase() {
+        let mut game = delayed_game(0.0, 0.25);
+        game.on_command(Command::HardDrop);
+        game.on_command(Command::Pause);
+        game.on_update(1.0);
+        game.on_command(Command::Pause);
+        assert_eq!(game.get_phase(), Phase::LineClear(TSpin::None));
+    }
+
+    #[test]
+    fn test_endless_has_no_goal() {
+        let game = game_with_mode(GameMode::Endless);
+        assert!(game.is_playing());
+        assert_eq!(game.get_lines_remaining(), None);
+        assert_eq!(game.get_time_remaining(), None);
+        assert_eq!(GameSettings::new().mode, GameMode::Endless);
+    }
+
+    #[test]
+    fn test_start_level() {
+        let mut game = Rustris::new();
+        game.set_start_level(7);
+        assert_eq!(game.get_game_stats().get_level(), 1);
+        game.reset();
+        assert_eq!(game.get_game_stats().get_level(), 7);
+        game.set_start_level(100);
+        assert_eq!(game.get_settings().start_level, MAX_GAME_LEVEL);
+    }
+
     #[test]
     fn test_drop_delay() {
         let mut game = Rustris::new();
-        assert_eq!(game.drop_delay(), 1.0);
+        assert!((game.drop_delay() - 1.0).abs() < 1e-9);
+        game.stats.set_level(5);
+        assert!(game.drop_delay() < 0.5);
+    }
+
+    #[test]
+    fn test_twenty_g() {
+        let mut game = Rustris::new();
+        game.stats.set_level(MAX_GAME_LEVEL);
+        game.on_update(1.0 / 60.0);
+        assert!(game.is_on_ground());
+        assert_eq!(game.get_current_piece().y, game.get_ghost_piece().y);
     }
 }
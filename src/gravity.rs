@@ -0,0 +1,123 @@
+use std::cmp::min;
+
+// Gravity is measured in rows per frame at this many frames a second.
+pub const FRAME_RATE: f64 = 60.0;
+// Gravity as fast as it goes: a piece falls the whole playfield in a single frame, so it lands
+// the moment it spawns or is moved off a ledge.
+pub const TWENTY_G: f64 = 20.0;
+// The level the guideline formula stops at, with every level from here on at 20G.
+const GUIDELINE_20G_LEVEL: u32 = 20;
+
+// Frames per row in NES Tetris, from its level 0. Level 29 and up stay at one row a frame.
+static NES_FRAMES_PER_ROW: [u32; 30] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6,
+    5, 5, 5, 4, 4, 4, 3, 3, 3, 2,
+    2, 2, 2, 2, 2, 2, 2, 2, 2, 1
+];
+
+// Gravity in the arcade Master mode from each level on, in 256ths of a row per frame. It eases
+// off again at level 200 and 420 before jumping to 20G at level 500.
+static MASTER_GRAVITY: [(u32, u32); 30] = [
+    (0, 4), (30, 6), (35, 8), (40, 10), (50, 12), (60, 16), (70, 32), (80, 48), (90, 64),
+    (100, 80), (120, 96), (140, 112), (160, 128), (170, 144), (200, 4), (220, 32), (230, 64),
+    (233, 96), (236, 128), (239, 160), (243, 192), (247, 224), (251, 256), (300, 512),
+    (330, 768), (360, 1024), (400, 1280), (420, 1024), (450, 768), (500, 5120)
+];
+
+// How fast pieces fall as the level goes up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GravityCurve {
+    // The guideline formula, (0.8 - (level - 1) * 0.007) ^ (level - 1) seconds per row, reaching
+    // 20G at level 20
+    Guideline,
+    // NES Tetris' frame table, where our level 1 is its level 0
+    Nes,
+    // The arcade Master table, for its 0 to 999 level counter
+    Master
+}
+impl GravityCurve {
+    // How many rows a piece falls each frame at `level`, up to TWENTY_G. Anything less than one
+    // is a fraction of a row, so a piece falls a row every few frames.
+    pub fn rows_per_frame(&self, level: u32) -> f64 {
+        match *self {
+            GravityCurve::Guideline if level >= GUIDELINE_20G_LEVEL => TWENTY_G,
+            GravityCurve::Guideline => {
+                let steps = level.saturating_sub(1) as f64;
+                let seconds_per_row = (0.8 - steps * 0.007).powf(steps);
+                (1.0 / (seconds_per_row * FRAME_RATE)).min(TWENTY_G)
+            }
+            GravityCurve::Nes => {
+                let index = min(level.saturating_sub(1) as usize, NES_FRAMES_PER_ROW.len() - 1);
+                1.0 / NES_FRAMES_PER_ROW[index] as f64
+            }
+            GravityCurve::Master => {
+                let &(_, gravity) = MASTER_GRAVITY.iter().rev()
+                    .find(|&&(from, _)| from <= level)
+                    .unwrap_or(&MASTER_GRAVITY[0]);
+                gravity as f64 / 256.0
+            }
+        }
+    }
+
+    // Seconds a piece takes to fall one row at `level`.
+    pub fn seconds_per_row(&self, level: u32) -> f64 {
+        1.0 / (self.rows_per_frame(level) * FRAME_RATE)
+    }
+
+    pub fn is_twenty_g(&self, level: u32) -> bool {
+        self.rows_per_frame(level) >= TWENTY_G
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-6, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn test_guideline_gravity() {
+        let gravity = GravityCurve::Guideline;
+        assert_close(gravity.seconds_per_row(1), 1.0);
+        assert_close(gravity.seconds_per_row(2), 0.793);
+        assert_close(gravity.seconds_per_row(10), 0.737f64.powf(9.0));
+        assert!(gravity.rows_per_frame(15) > 1.0);
+        assert!(!gravity.is_twenty_g(18));
+        assert!(gravity.is_twenty_g(20));
+        assert_eq!(gravity.rows_per_frame(100), TWENTY_G);
+    }
+
+    #[test]
+    fn test_nes_gravity() {
+        let gravity = GravityCurve::Nes;
+        assert_close(gravity.seconds_per_row(1), 48.0 / 60.0);
+        assert_close(gravity.seconds_per_row(10), 6.0 / 60.0);
+        assert_close(gravity.rows_per_frame(30), 1.0);
+        assert_close(gravity.rows_per_frame(50), 1.0);
+        assert!(!gravity.is_twenty_g(50));
+    }
+
+    #[test]
+    fn test_master_gravity() {
+        let gravity = GravityCurve::Master;
+        assert_close(gravity.rows_per_frame(0), 4.0 / 256.0);
+        assert_close(gravity.rows_per_frame(199), 144.0 / 256.0);
+        assert_close(gravity.rows_per_frame(200), 4.0 / 256.0);
+        assert_close(gravity.rows_per_frame(251), 1.0);
+        assert!(!gravity.is_twenty_g(499));
+        assert!(gravity.is_twenty_g(500));
+        assert!(gravity.is_twenty_g(999));
+    }
+
+    #[test]
+    fn test_gravity_speeds_up() {
+        for gravity in [GravityCurve::Guideline, GravityCurve::Nes].iter() {
+            for level in 1..30 {
+                assert!(gravity.rows_per_frame(level + 1) >= gravity.rows_per_frame(level));
+            }
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use rustris::settings::WIDTH_IN_BLOCKS;
+
+pub const WINDOW_WIDTH: u32 = 800;
+pub const WINDOW_HEIGHT: u32 = 600;
+pub const BLOCK_SIZE: f64 = 27.0;
+pub const GRID_LINE_WIDTH: f64 = 1.0;
+pub const GRID_X_OFFSET: f64 = (
+    WINDOW_WIDTH as f64 / 2.0 - (WIDTH_IN_BLOCKS as f64/2.0 * BLOCK_SIZE)
+);
+pub const GRID_Y_OFFSET: f64 = 25.0;
+pub const GHOST_BORDER_WIDTH: f64 = 0.3;
+pub const NEXT_PIECE_WIDTH: f64 = 150.0;
+pub const NEXT_PIECE_HEIGHT: f64 = 150.0;
+pub const NEXT_PIECE_SPACING: f64 = 2.5 * BLOCK_SIZE;
+pub const SCOREBOARD_ROW_HEIGHT: f64 = 30.0;
+pub const SECTION_TIME_HEIGHT: f64 = 18.0;
@@ -0,0 +1,163 @@
+use std::cmp::{max, min};
+
+use settings::MAX_GAME_LEVEL;
+use stats::TSpin;
+
+// The top of the arcade Master level counter, where the game ends.
+pub const MASTER_MAX_LEVEL: u32 = 999;
+// Levels in each section of a Master game. A piece can't take the level past the last level of a
+// section, only a line clear can.
+pub const SECTION_LEVELS: u32 = 100;
+
+// How a game moves up through the levels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LevelProgression {
+    // A new level every so many lines
+    Fixed(u32),
+    // The guideline variable goal: 5 times the level in awarded lines, where harder clears award
+    // more lines than they remove
+    Variable,
+    // NES Tetris, where a high start level holds until enough lines are cleared to have got there
+    // from the bottom (or 100 lines), and every 10 lines goes up a level after that
+    Nes,
+    // The arcade Master counter, from 0 to MASTER_MAX_LEVEL: a level for every piece and every
+    // line, except that pieces stop at the end of each section and just before the last level
+    Master
+}
+impl LevelProgression {
+    // The level a game starting on `start_level` begins on.
+    pub fn get_first_level(&self, start_level: u32) -> u32 {
+        match *self {
+            LevelProgression::Master => 0,
+            _ => start_level
+        }
+    }
+
+    pub fn get_max_level(&self) -> u32 {
+        match *self {
+            LevelProgression::Master => MASTER_MAX_LEVEL,
+            _ => MAX_GAME_LEVEL
+        }
+    }
+
+    // Levels gained by placing a piece on `level`.
+    pub fn levels_per_piece(&self, level: u32) -> u32 {
+        match *self {
+            LevelProgression::Master => {
+                let stopped = level % SECTION_LEVELS == SECTION_LEVELS - 1 ||
+                              level >= MASTER_MAX_LEVEL - 1;
+                if stopped { 0 } else { 1 }
+            }
+            _ => 0
+        }
+    }
+
+    // How many sections have been played through by `level`, in progressions split into them.
+    pub fn get_sections_cleared(&self, level: u32) -> Option<u32> {
+        match *self {
+            LevelProgression::Master if level >= MASTER_MAX_LEVEL => {
+                Some(MASTER_MAX_LEVEL / SECTION_LEVELS + 1)
+            }
+            LevelProgression::Master => Some(level / SECTION_LEVELS),
+            _ => None
+        }
+    }
+
+    // Lines needed to clear `level` in a game that started on `start_level`.
+    pub fn goal(&self, level: u32, start_level: u32) -> u32 {
+        match *self {
+            LevelProgression::Fixed(lines) => max(lines, 1),
+            LevelProgression::Variable => 5 * level,
+            LevelProgression::Nes if level == start_level => {
+                // NES counts levels from 0
+                let start = (start_level - 1) * 10;
+                min(start + 10, max(100, start.saturating_sub(50)))
+            }
+            LevelProgression::Nes => 10,
+            LevelProgression::Master => 1
+        }
+    }
+
+    // How many lines a clear counts for towards the goal.
+    pub fn awarded_lines(&self, lines: u32, t_spin: TSpin, back_to_back: bool) -> u32 {
+        match *self {
+            LevelProgression::Variable => {
+                let awarded = match (t_spin, lines) {
+                    (TSpin::None, 1) => 1,
+                    (TSpin::None, 2) => 3,
+                    (TSpin::None, 3) => 5,
+                    (TSpin::None, 4) => 8,
+                    (TSpin::Mini, 0) => 1,
+                    (TSpin::Mini, 1) => 2,
+                    (TSpin::Mini, 2) => 4,
+                    (TSpin::Full, 0) => 4,
+                    (TSpin::Full, 1) => 8,
+                    (TSpin::Full, 2) => 12,
+                    (TSpin::Full, 3) => 16,
+                    _ => 0
+                };
+                if back_to_back { awarded * 3 / 2 } else { awarded }
+            }
+            _ => lines
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stats::TSpin;
+
+    #[test]
+    fn test_fixed_goal() {
+        let progression = LevelProgression::Fixed(15);
+        assert_eq!(progression.goal(1, 1), 15);
+        assert_eq!(progression.goal(7, 3), 15);
+        assert_eq!(progression.awarded_lines(4, TSpin::None, true), 4);
+        assert_eq!(progression.awarded_lines(0, TSpin::Full, false), 0);
+    }
+
+    #[test]
+    fn test_variable_goal() {
+        let progression = LevelProgression::Variable;
+        assert_eq!(progression.goal(1, 1), 5);
+        assert_eq!(progression.goal(4, 1), 20);
+        assert_eq!(progression.awarded_lines(1, TSpin::None, false), 1);
+        assert_eq!(progression.awarded_lines(4, TSpin::None, false), 8);
+        assert_eq!(progression.awarded_lines(4, TSpin::None, true), 12);
+        assert_eq!(progression.awarded_lines(0, TSpin::Full, false), 4);
+        assert_eq!(progression.awarded_lines(2, TSpin::Full, true), 18);
+    }
+
+    #[test]
+    fn test_nes_goal() {
+        let progression = LevelProgression::Nes;
+        assert_eq!(progression.goal(1, 1), 10);
+        assert_eq!(progression.goal(6, 6), 60);
+        assert_eq!(progression.goal(10, 10), 100);
+        assert_eq!(progression.goal(16, 16), 100);
+        assert_eq!(progression.goal(19, 19), 130);
+        assert_eq!(progression.goal(20, 19), 10);
+    }
+
+    #[test]
+    fn test_master_progression() {
+        let progression = LevelProgression::Master;
+        assert_eq!(progression.get_first_level(5), 0);
+        assert_eq!(progression.get_max_level(), MASTER_MAX_LEVEL);
+        assert_eq!(progression.goal(50, 0), 1);
+        assert_eq!(progression.levels_per_piece(0), 1);
+        assert_eq!(progression.levels_per_piece(98), 1);
+        assert_eq!(progression.levels_per_piece(99), 0);
+        assert_eq!(progression.levels_per_piece(100), 1);
+        assert_eq!(progression.levels_per_piece(997), 1);
+        assert_eq!(progression.levels_per_piece(998), 0);
+        assert_eq!(progression.get_sections_cleared(99), Some(0));
+        assert_eq!(progression.get_sections_cleared(301), Some(3));
+        assert_eq!(progression.get_sections_cleared(998), Some(9));
+        assert_eq!(progression.get_sections_cleared(999), Some(10));
+        assert_eq!(LevelProgression::Nes.get_sections_cleared(10), None);
+        assert_eq!(LevelProgression::Nes.levels_per_piece(10), 0);
+    }
+}
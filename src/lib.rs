@@ -0,0 +1,19 @@
+//! The Rustris game engine.
+//!
+//! Everything in this crate is independent of any window or renderer. A front end drives a
+//! `Rustris` game by feeding it `Command`s and elapsed time, and reads the board, pieces and
+//! stats back out to draw them.
+
+extern crate rand;
+
+pub mod board;
+pub mod game;
+pub mod gravity;
+pub mod leveling;
+pub mod master;
+pub mod mode;
+pub mod randomizer;
+pub mod scoring;
+pub mod settings;
+pub mod stats;
+pub mod tetromino;
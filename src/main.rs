@@ -1,26 +1,32 @@
 #[macro_use] extern crate conrod;
 extern crate find_folder;
 extern crate piston_window;
-extern crate rand;
+extern crate rustris;
 
-use piston_window::{EventLoop, PistonWindow, WindowSettings, UpdateEvent, clear};
+use piston_window::{Button, EventLoop, PistonWindow, WindowSettings, UpdateEvent, clear};
 use piston_window::Event::{Input, Update, Render};
+use piston_window::Input::{Press, Release};
 
-mod board;
+mod cli;
 mod colors;
-mod game;
-mod randomizer;
-mod tetromino;
-mod settings;
-mod stats;
+mod controls;
+mod layout;
+mod records;
+mod render;
 mod ui;
 
-use game::Rustris;
-use settings::*;
+use rustris::game::Rustris;
+use rustris::settings::VERSION;
+use cli::parse_args;
+use controls::Controls;
+use layout::*;
+use records::Records;
+use render::render_game;
 use ui::{create_ui, set_ui};
 
 
 fn main() {
+    let settings = parse_args();
     let window_title = format!("Rustris {}", VERSION);
 
     let mut window: PistonWindow =
@@ -31,32 +37,49 @@ fn main() {
         .unwrap();
 
     let mut ui = create_ui(&window);
-    let mut game = Rustris::new();
+    let mut game = Rustris::with_settings(settings);
+    let mut controls = Controls::new();
+    let mut records = Records::load();
+    let mut recorded = false;
 
     window.set_ups(60);
 
     while let Some(event) = window.next() {
         // let the UI handle the event
         ui.handle_event(&event);
-        event.update(|_| ui.set_widgets(|ui| set_ui(ui, &mut game)));
+        event.update(|_| ui.set_widgets(|ui| set_ui(ui, &mut game, &records)));
 
         match event {
-            Input(input) => {
-                game.on_input(input);
+            Input(Press(Button::Keyboard(key))) => {
+                if let Some(command) = controls.on_press(key) {
+                    game.on_command(command);
+                }
+            }
+            Input(Release(Button::Keyboard(key))) => {
+                if let Some(command) = controls.on_release(key) {
+                    game.on_command_released(command);
+                }
             }
             Update(update_args) => {
-                game.on_update(update_args);
+                game.on_update(update_args.dt);
             }
             Render(_) => {
                 window.draw_2d(&event, |c, g| {
                     clear([0.0, 0.0, 0.0, 1.0], g);
                     ui.draw(c, g);
-                    if game.is_playing() {
-                        game.render(c, g);
+                    if game.is_playing() || game.is_counting_down() {
+                        render_game(&game, c, g);
                     }
                 });
             }
             _ => {}
         }
+
+        // Save the result of a game that reached its end once, as soon as it ends
+        let ended = game.is_finished() || game.is_time_up();
+        if ended && !recorded {
+            records.record_game(&game);
+        }
+        recorded = ended;
     }
 }
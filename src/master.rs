@@ -0,0 +1,156 @@
+use gravity::{FRAME_RATE, GravityCurve};
+use leveling::{LevelProgression, MASTER_MAX_LEVEL, SECTION_LEVELS};
+use randomizer::RandomizerType;
+use scoring::ScoringType;
+use settings::GameSettings;
+use stats::GameStats;
+
+// Frames of entry delay (ARE) and lock delay from each level on. Both shorten in the later
+// sections, on top of gravity reaching 20G at level 500.
+static SECTION_TIMINGS: [(u32, u32, u32); 4] = [
+    (0, 25, 30),
+    (700, 16, 30),
+    (800, 12, 30),
+    (900, 12, 17)
+];
+
+// Grades from the lowest up, with the score each one needs.
+static GRADES: [(&'static str, u32); 18] = [
+    ("9", 0), ("8", 400), ("7", 800), ("6", 1400), ("5", 2000), ("4", 3500), ("3", 5500),
+    ("2", 8000), ("1", 12000), ("S1", 16000), ("S2", 22000), ("S3", 30000), ("S4", 40000),
+    ("S5", 52000), ("S6", 66000), ("S7", 82000), ("S8", 100000), ("S9", 120000)
+];
+pub const GRAND_MASTER: &'static str = "GM";
+
+// The Grand Master grade needs every one of these checkpoints reached: the sections cleared,
+// the most seconds it may take and the least score it needs by then.
+static GRAND_MASTER_CHECKPOINTS: [(usize, f64, u32); 3] = [
+    (3, 4.0 * 60.0 + 15.0, 12000),
+    (5, 7.0 * 60.0 + 30.0, 40000),
+    (10, 13.0 * 60.0 + 30.0, 126000)
+];
+
+// Master plays by the arcade's rules whatever else was chosen: its own gravity, scoring and level
+// counter, with pieces dealt by the history randomizer.
+pub fn master_settings(settings: GameSettings) -> GameSettings {
+    GameSettings {
+        randomizer: RandomizerType::History,
+        scoring: ScoringType::Master,
+        gravity: GravityCurve::Master,
+        level_progression: LevelProgression::Master,
+        ..settings
+    }
+}
+
+fn section_timing(level: u32) -> (u32, u32, u32) {
+    let timing = SECTION_TIMINGS.iter().rev().find(|&&(from, _, _)| from <= level);
+    *timing.unwrap_or(&SECTION_TIMINGS[0])
+}
+
+// Seconds between a piece locking and the next one appearing at `level`.
+pub fn get_are(level: u32) -> f64 {
+    let (_, are, _) = section_timing(level);
+    are as f64 / FRAME_RATE
+}
+
+// Seconds a piece can rest on the ground before it locks at `level`.
+pub fn get_lock_delay(level: u32) -> f64 {
+    let (_, _, lock_delay) = section_timing(level);
+    lock_delay as f64 / FRAME_RATE
+}
+
+// The level a piece stops at until a line is cleared: the end of the current section, or the
+// last level.
+pub fn get_level_stop(level: u32) -> u32 {
+    let stop = (level / SECTION_LEVELS + 1) * SECTION_LEVELS;
+    if stop >= MASTER_MAX_LEVEL { MASTER_MAX_LEVEL } else { stop }
+}
+
+// The grade a game has earned, from its score, or Grand Master for reaching the last level fast
+// enough with a high enough score along the way.
+pub fn get_grade(stats: &GameStats) -> &'static str {
+    let splits = stats.get_splits();
+    let grand_master = GRAND_MASTER_CHECKPOINTS.iter().all(|&(sections, time, score)| {
+        splits.get(sections - 1).map_or(false, |split| split.time <= time && split.score >= score)
+    });
+    if grand_master {
+        return GRAND_MASTER;
+    }
+    let score = stats.get_score();
+    let grade = GRADES.iter().rev().find(|&&(_, needed)| score >= needed);
+    grade.map_or(GRADES[0].0, |&(grade, _)| grade)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use leveling::LevelProgression;
+    use scoring::ScoringType;
+    use settings::GameSettings;
+    use stats::{GameStats, TSpin};
+
+    fn master_stats() -> GameStats {
+        GameStats::with_settings(master_settings(GameSettings::new()))
+    }
+
+    #[test]
+    fn test_master_settings() {
+        let settings = master_settings(GameSettings::new());
+        assert_eq!(settings.randomizer, RandomizerType::History);
+        assert_eq!(settings.scoring, ScoringType::Master);
+        assert_eq!(settings.gravity, GravityCurve::Master);
+        assert_eq!(settings.level_progression, LevelProgression::Master);
+    }
+
+    #[test]
+    fn test_section_timings() {
+        assert_eq!(get_are(0), 25.0 / FRAME_RATE);
+        assert_eq!(get_are(699), 25.0 / FRAME_RATE);
+        assert_eq!(get_are(700), 16.0 / FRAME_RATE);
+        assert_eq!(get_are(999), 12.0 / FRAME_RATE);
+        assert_eq!(get_lock_delay(899), 30.0 / FRAME_RATE);
+        assert_eq!(get_lock_delay(900), 17.0 / FRAME_RATE);
+    }
+
+    #[test]
+    fn test_level_stop() {
+        assert_eq!(get_level_stop(0), 100);
+        assert_eq!(get_level_stop(99), 100);
+        assert_eq!(get_level_stop(100), 200);
+        assert_eq!(get_level_stop(850), 900);
+        assert_eq!(get_level_stop(901), MASTER_MAX_LEVEL);
+    }
+
+    #[test]
+    fn test_grade_from_score() {
+        let mut stats = master_stats();
+        assert_eq!(get_grade(&stats), "9");
+        stats.set_level(500);
+        stats.score_completed_lines(4, TSpin::None);
+        assert_eq!(stats.get_score(), 3528);
+        assert_eq!(get_grade(&stats), "4");
+    }
+
+    // Plays a Tetris with every piece, each taking `seconds`, until the last level.
+    fn play_tetrises(stats: &mut GameStats, seconds: f64) {
+        while stats.get_level() < MASTER_MAX_LEVEL {
+            stats.add_time(seconds);
+            stats.count_piece();
+            stats.score_completed_lines(4, TSpin::None);
+            stats.score_completed_lines(0, TSpin::None);
+        }
+    }
+
+    #[test]
+    fn test_grand_master() {
+        let mut stats = master_stats();
+        play_tetrises(&mut stats, 1.0);
+        assert_eq!(get_grade(&stats), GRAND_MASTER);
+
+        // Too slow to make the time checkpoints
+        let mut stats = master_stats();
+        play_tetrises(&mut stats, 5.0);
+        assert_eq!(get_grade(&stats), "S9");
+    }
+}
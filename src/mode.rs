@@ -0,0 +1,141 @@
+use leveling::MASTER_MAX_LEVEL;
+use stats::GameStats;
+
+// Lines to clear to finish a Sprint.
+pub const SPRINT_LINES: u32 = 40;
+pub const DEFAULT_MARATHON_LINES: u32 = 150;
+pub const DEFAULT_DIG_ROWS: u32 = 10;
+// Seconds before the first row of garbage rises in Survival, how much sooner each row after it
+// comes, and the shortest the wait gets.
+pub const SURVIVAL_START_INTERVAL: f64 = 10.0;
+pub const SURVIVAL_ACCELERATION: f64 = 0.95;
+pub const SURVIVAL_MIN_INTERVAL: f64 = 1.0;
+
+// The ways a game can be played.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameMode {
+    // Play through the levels until reaching the finish line
+    Marathon(MarathonGoal),
+    // Play until topping out
+    Endless,
+    // Clear SPRINT_LINES lines as fast as possible
+    Sprint,
+    // Score as much as possible before the time limit runs out
+    Ultra,
+    // Dig through this many rows of garbage as fast as possible
+    Dig(u32),
+    // Last as long as possible while garbage rises from below, faster and faster
+    Survival,
+    // Practice that can't be lost: topping out clears the board and play carries on
+    Zen(ZenGravity),
+    // The arcade Master rules: a level counter up to MASTER_MAX_LEVEL, reaching 20G halfway,
+    // graded on score and time at the end
+    Master
+}
+impl GameMode {
+    // Races against the clock count down before play starts, so the timer starts fairly.
+    pub fn has_countdown(&self) -> bool {
+        match *self {
+            GameMode::Marathon(_) | GameMode::Endless | GameMode::Survival | GameMode::Zen(_) => {
+                false
+            }
+            GameMode::Sprint | GameMode::Ultra | GameMode::Dig(_) | GameMode::Master => true
+        }
+    }
+
+    // Whether topping out ends the game.
+    pub fn can_top_out(&self) -> bool {
+        match *self {
+            GameMode::Zen(_) => false,
+            _ => true
+        }
+    }
+
+    // Whether gravity speeds up as the level goes up, rather than staying at the starting level's
+    // speed.
+    pub fn has_gravity_acceleration(&self) -> bool {
+        match *self {
+            GameMode::Zen(_) => false,
+            _ => true
+        }
+    }
+
+    // Whether pieces fall by themselves.
+    pub fn has_gravity(&self) -> bool {
+        *self != GameMode::Zen(ZenGravity::Off)
+    }
+
+    // Rows of garbage on the board when the game starts.
+    pub fn get_starting_garbage(&self) -> u32 {
+        match *self {
+            GameMode::Dig(rows) => rows,
+            _ => 0
+        }
+    }
+
+    // Seconds until the next row of garbage rises, once `rows_risen` rows already have, in modes
+    // where garbage rises during play.
+    pub fn get_garbage_interval(&self, rows_risen: u32) -> Option<f64> {
+        match *self {
+            GameMode::Survival => {
+                let speed_up = SURVIVAL_ACCELERATION.powi(rows_risen as i32);
+                Some((SURVIVAL_START_INTERVAL * speed_up).max(SURVIVAL_MIN_INTERVAL))
+            }
+            _ => None
+        }
+    }
+
+    // Whether the game ends when its time limit runs out.
+    pub fn has_time_limit(&self) -> bool {
+        *self == GameMode::Ultra
+    }
+
+    // Whether the game has been won.
+    pub fn is_complete(&self, stats: &GameStats) -> bool {
+        match *self {
+            GameMode::Marathon(MarathonGoal::Lines(lines)) => stats.get_lines() >= lines,
+            GameMode::Marathon(MarathonGoal::Level(level)) => stats.get_level() > level,
+            GameMode::Endless | GameMode::Ultra | GameMode::Survival | GameMode::Zen(_) => false,
+            GameMode::Sprint => stats.get_lines() >= SPRINT_LINES,
+            GameMode::Dig(rows) => stats.get_garbage_cleared() >= rows,
+            GameMode::Master => stats.get_level() >= MASTER_MAX_LEVEL
+        }
+    }
+
+    // Lines left to clear before the game is won, for modes with a line goal.
+    pub fn get_lines_remaining(&self, stats: &GameStats) -> Option<u32> {
+        match *self {
+            GameMode::Marathon(MarathonGoal::Lines(lines)) => {
+                Some(lines.saturating_sub(stats.get_lines()))
+            }
+            GameMode::Sprint => Some(SPRINT_LINES.saturating_sub(stats.get_lines())),
+            _ => None
+        }
+    }
+
+    // Garbage rows left to clear before the game is won, for modes with a garbage goal.
+    pub fn get_garbage_remaining(&self, stats: &GameStats) -> Option<u32> {
+        match *self {
+            GameMode::Dig(rows) => Some(rows.saturating_sub(stats.get_garbage_cleared())),
+            _ => None
+        }
+    }
+}
+
+// Where a Marathon ends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MarathonGoal {
+    // Clearing this many lines
+    Lines(u32),
+    // Clearing this level, so the level after it is never played
+    Level(u32)
+}
+
+// How pieces fall in Zen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ZenGravity {
+    // At the starting level's speed, however far the level climbs
+    Constant,
+    // Not at all, so pieces only move down when dropped
+    Off
+}
@@ -1,14 +1,51 @@
 use std::collections::VecDeque;
-use rand::{Rng, thread_rng};
+use rand::{IsaacRng, Rng, SeedableRng};
 
 use tetromino::*;
 
-pub struct Randomizer {
+static TETROMINOS: [&'static Tetromino; 7] = [&I, &J, &L, &O, &S, &T, &Z];
+
+// A source of pieces for a game. Randomizers are always created from a seed so that a game's
+// piece sequence can be reproduced.
+pub trait Randomizer {
+    fn create_piece(&mut self) -> Piece;
+}
+
+// The randomizers a game can be started with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RandomizerType {
+    // TGM-style 4 piece history with 6 rerolls
+    History,
+    // Guideline generator dealing out shuffled bags of all 7 pieces
+    SevenBag,
+    // Shuffled bags holding two of each piece
+    FourteenBag,
+    // Every piece picked independently
+    Pure
+}
+impl RandomizerType {
+    pub fn create(&self, seed: u32) -> Box<Randomizer> {
+        match *self {
+            RandomizerType::History => Box::new(HistoryRandomizer::new(seed)),
+            RandomizerType::SevenBag => Box::new(BagRandomizer::new(seed, 1)),
+            RandomizerType::FourteenBag => Box::new(BagRandomizer::new(seed, 2)),
+            RandomizerType::Pure => Box::new(PureRandomizer::new(seed))
+        }
+    }
+}
+
+fn seeded_rng(seed: u32) -> IsaacRng {
+    IsaacRng::from_seed(&[seed])
+}
+
+pub struct HistoryRandomizer {
+    rng: IsaacRng,
     history: VecDeque<&'static Tetromino>
 }
-impl Randomizer {
-    pub fn new() -> Randomizer {
-        let mut rand = Randomizer {
+impl HistoryRandomizer {
+    pub fn new(seed: u32) -> HistoryRandomizer {
+        let mut rand = HistoryRandomizer {
+            rng: seeded_rng(seed),
             history: VecDeque::new()
         };
         rand.add_to_history(&Z);
@@ -18,11 +55,19 @@ impl Randomizer {
         rand
     }
 
-    pub fn create_piece(&mut self) -> Piece {
-        let all_tetrominos = [&I, &J, &L, &O, &S, &T, &Z];
+    fn add_to_history(&mut self, ptype: &'static Tetromino) {
+        self.history.push_back(ptype);
+        if self.history.len() > 4 {
+            self.history.pop_front();
+        }
+        debug_assert!(self.history.len() <= 4);
+    }
+}
+impl Randomizer for HistoryRandomizer {
+    fn create_piece(&mut self) -> Piece {
         let mut random_ptype = None;
         for _ in 0..6 {
-            random_ptype = thread_rng().choose(&all_tetrominos);
+            random_ptype = self.rng.choose(&TETROMINOS);
             match random_ptype {
                 Some(ptype) => {
                     if self.history.iter().all(|&item| item != *ptype) {
@@ -36,13 +81,53 @@ impl Randomizer {
         self.add_to_history(ptype);
         Piece::create(ptype)
     }
+}
 
-    fn add_to_history(&mut self, ptype: &'static Tetromino) {
-        self.history.push_back(ptype);
-        if self.history.len() > 4 {
-            self.history.pop_front();
+// Deals pieces out of a shuffled bag holding `copies` of each tetromino, refilling the bag once
+// it is empty.
+pub struct BagRandomizer {
+    rng: IsaacRng,
+    copies: usize,
+    bag: Vec<&'static Tetromino>
+}
+impl BagRandomizer {
+    pub fn new(seed: u32, copies: usize) -> BagRandomizer {
+        BagRandomizer {
+            rng: seeded_rng(seed),
+            copies: copies,
+            bag: Vec::with_capacity(copies * TETROMINOS.len())
         }
-        debug_assert!(self.history.len() <= 4);
+    }
+
+    fn refill(&mut self) {
+        for _ in 0..self.copies {
+            self.bag.extend_from_slice(&TETROMINOS);
+        }
+        self.rng.shuffle(&mut self.bag);
+    }
+}
+impl Randomizer for BagRandomizer {
+    fn create_piece(&mut self) -> Piece {
+        if self.bag.is_empty() {
+            self.refill();
+        }
+        Piece::create(self.bag.pop().unwrap())
+    }
+}
+
+pub struct PureRandomizer {
+    rng: IsaacRng
+}
+impl PureRandomizer {
+    pub fn new(seed: u32) -> PureRandomizer {
+        PureRandomizer {
+            rng: seeded_rng(seed)
+        }
+    }
+}
+impl Randomizer for PureRandomizer {
+    fn create_piece(&mut self) -> Piece {
+        Piece::create(self.rng.choose(&TETROMINOS).unwrap())
     }
 }
 
@@ -50,15 +135,65 @@ impl Randomizer {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tetromino::*;
+
+    fn count_shapes(pieces: &[Piece], shape: Shape) -> usize {
+        pieces.iter().filter(|p| p.get_shape() == shape).count()
+    }
 
     #[test]
     fn test_add_to_history_ensure_history_length() {
-        let mut rand = Randomizer::new();
+        let mut rand = HistoryRandomizer::new(0);
         assert_eq!(rand.history.len(), 4);
         rand.add_to_history(&L);
         let length = rand.history.len();
         assert_eq!(length, 4);
         assert_eq!(rand.history[length - 1], &L);
     }
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let types = [
+            RandomizerType::History,
+            RandomizerType::SevenBag,
+            RandomizerType::FourteenBag,
+            RandomizerType::Pure
+        ];
+        for randomizer_type in types.iter() {
+            let mut first = randomizer_type.create(1234);
+            let mut second = randomizer_type.create(1234);
+            for _ in 0..50 {
+                assert_eq!(first.create_piece().get_shape(), second.create_piece().get_shape());
+            }
+        }
+    }
+
+    #[test]
+    fn test_different_seed_different_sequence() {
+        let mut first = RandomizerType::Pure.create(1);
+        let mut second = RandomizerType::Pure.create(2);
+        let first_shapes: Vec<Shape> = (0..50).map(|_| first.create_piece().get_shape()).collect();
+        let second_shapes: Vec<Shape> =
+            (0..50).map(|_| second.create_piece().get_shape()).collect();
+        assert!(first_shapes != second_shapes);
+    }
+
+    #[test]
+    fn test_seven_bag_deals_each_piece_once() {
+        let mut rand = RandomizerType::SevenBag.create(0);
+        for _ in 0..3 {
+            let bag: Vec<Piece> = (0..7).map(|_| rand.create_piece()).collect();
+            for &tetromino in TETROMINOS.iter() {
+                assert_eq!(count_shapes(&bag, Piece::create(tetromino).get_shape()), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fourteen_bag_deals_each_piece_twice() {
+        let mut rand = RandomizerType::FourteenBag.create(0);
+        let bag: Vec<Piece> = (0..14).map(|_| rand.create_piece()).collect();
+        for &tetromino in TETROMINOS.iter() {
+            assert_eq!(count_shapes(&bag, Piece::create(tetromino).get_shape()), 2);
+        }
+    }
 }
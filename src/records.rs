@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use rustris::game::Rustris;
+use rustris::mode::GameMode;
+use rustris::randomizer::RandomizerType;
+use rustris::scoring::ScoringType;
+
+const RECORDS_FILE: &'static str = ".rustris_records";
+pub const LEADERBOARD_SIZE: usize = 5;
+
+// Personal bests, kept between runs in a plain text file in the home directory with one
+// "<name> <value>" record per line. A leaderboard is several lines under the same name.
+pub struct Records {
+    path: PathBuf,
+    records: BTreeMap<String, Vec<f64>>,
+    // The best time there was before the last time was recorded, to compare that game against
+    previous_best: Option<f64>
+}
+impl Records {
+    pub fn load() -> Records {
+        let home = env::var_os("HOME").map_or(PathBuf::from("."), PathBuf::from);
+        let mut records = Records {
+            path: home.join(RECORDS_FILE),
+            records: BTreeMap::new(),
+            previous_best: None
+        };
+        if let Ok(file) = File::open(&records.path) {
+            for line in BufReader::new(file).lines().filter_map(|line| line.ok()) {
+                let mut fields = line.split_whitespace();
+                if let (Some(name), Some(value)) = (fields.next(), fields.next()) {
+                    if let Ok(value) = value.parse() {
+                        let values = records.records.entry(name.to_string());
+                        values.or_insert_with(Vec::new).push(value);
+                    }
+                }
+            }
+        }
+        records
+    }
+
+    pub fn get_best_time(&self, name: &str) -> Option<f64> {
+        self.records.get(name).and_then(|times| times.first().cloned())
+    }
+
+    // The best time under the name last recorded to, from before that game's time was recorded.
+    pub fn get_previous_best_time(&self) -> Option<f64> {
+        self.previous_best
+    }
+
+    // Saves `time` if it beats the best one under `name`, returning whether it did.
+    pub fn record_time(&mut self, name: &str, time: f64) -> bool {
+        self.previous_best = self.get_best_time(name);
+        match self.previous_best {
+            Some(best) if best <= time => false,
+            _ => {
+                self.records.insert(name.to_string(), vec![time]);
+                self.save();
+                true
+            }
+        }
+    }
+
+    // The best scores under `name`, highest first.
+    pub fn get_leaderboard(&self, name: &str) -> Vec<u32> {
+        self.records.get(name).map_or(Vec::new(), |scores| {
+            scores.iter().map(|&score| score as u32).collect()
+        })
+    }
+
+    // Adds `score` to the leaderboard under `name` if it is good enough, returning whether it was.
+    pub fn record_score(&mut self, name: &str, score: u32) -> bool {
+        let made_it = {
+            let scores = self.records.entry(name.to_string()).or_insert_with(Vec::new);
+            let rank = scores.iter().take_while(|&&best| best >= score as f64).count();
+            if rank < LEADERBOARD_SIZE {
+                scores.insert(rank, score as f64);
+                scores.truncate(LEADERBOARD_SIZE);
+                true
+            } else {
+                false
+            }
+        };
+        if made_it {
+            self.save();
+        }
+        made_it
+    }
+
+    // Records the result of a game that has just ended in its mode's own way.
+    pub fn record_game(&mut self, game: &Rustris) {
+        let name = record_name(game);
+        let stats = game.get_game_stats();
+        match game.get_settings().mode {
+            GameMode::Sprint | GameMode::Dig(_) | GameMode::Master => {
+                self.record_time(&name, stats.get_time());
+            }
+            GameMode::Ultra => {
+                self.record_score(&name, stats.get_score());
+            }
+            GameMode::Marathon(_) | GameMode::Endless | GameMode::Survival | GameMode::Zen(_) => {}
+        }
+    }
+
+    // Records are a nicety, so failing to write them shouldn't stop the game.
+    fn save(&self) {
+        if let Ok(mut file) = File::create(&self.path) {
+            for (name, values) in &self.records {
+                for value in values {
+                    let _ = writeln!(file, "{} {}", name, value);
+                }
+            }
+        }
+    }
+}
+
+// Games are only comparable with others dealt by the same randomizer, and for Ultra and Dig,
+// played for the same length of time or through the same amount of garbage. Ultra scores also
+// depend on the scoring system and the level the game started on. A game replayed from a fixed
+// seed knows its pieces in advance, so it only competes with others on the same seed.
+pub fn record_name(game: &Rustris) -> String {
+    let settings = game.get_settings();
+    let randomizer = randomizer_name(settings.randomizer);
+    let name = match settings.mode {
+        GameMode::Marathon(_) => format!("marathon-{}", randomizer),
+        GameMode::Endless => format!("endless-{}", randomizer),
+        GameMode::Sprint => format!("sprint-{}", randomizer),
+        GameMode::Ultra => {
+            format!("ultra-{}s-{}-level{}-{}", settings.time_limit as u32,
+                    scoring_name(settings.scoring), settings.start_level, randomizer)
+        }
+        GameMode::Dig(rows) => format!("dig-{}-{}", rows, randomizer),
+        GameMode::Survival => format!("survival-{}", randomizer),
+        GameMode::Zen(_) => format!("zen-{}", randomizer),
+        // Master always deals with the history randomizer
+        GameMode::Master => "master".to_string()
+    };
+    match settings.seed {
+        Some(seed) => format!("{}-seed{}", name, seed),
+        None => name
+    }
+}
+
+pub fn randomizer_name(randomizer: RandomizerType) -> &'static str {
+    match randomizer {
+        RandomizerType::History => "history",
+        RandomizerType::SevenBag => "7bag",
+        RandomizerType::FourteenBag => "14bag",
+        RandomizerType::Pure => "random"
+    }
+}
+
+pub fn scoring_name(scoring: ScoringType) -> &'static str {
+    match scoring {
+        ScoringType::Guideline => "guideline",
+        ScoringType::Nes => "nes",
+        ScoringType::Sega => "sega",
+        ScoringType::Master => "master"
+    }
+}
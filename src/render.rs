@@ -0,0 +1,156 @@
+use piston_window::{Context, G2d, Line, Rectangle, Transformed, color, rectangle};
+use piston_window::grid::Grid;
+use piston_window::types::Color;
+
+use rustris::board::{Board, CellState};
+use rustris::game::{Phase, Rustris};
+use rustris::settings::*;
+use rustris::tetromino::{Block, Piece};
+
+use colors::{GREY, SILVER, shape_color};
+use layout::*;
+
+
+pub fn render_game(game: &Rustris, context: Context, graphics: &mut G2d) {
+    let phase = game.get_phase();
+    render_board(game.get_board(), phase, context, graphics);
+    // Between pieces the last one has already locked into the board
+    if phase == Phase::Falling {
+        render_piece_in_grid(&game.get_ghost_piece(), RenderType::Ghost, context, graphics);
+        render_piece_in_grid(game.get_current_piece(), RenderType::Normal, context, graphics);
+    }
+    for (i, piece) in game.get_next_pieces().iter().enumerate() {
+        render_piece_in_next_piece(piece, i, context, graphics);
+    }
+    if let Some(piece) = game.get_held_piece() {
+        // Show the held piece as an outline while it can't be swapped back in
+        let render_type = if game.can_hold() { RenderType::Normal } else { RenderType::Ghost };
+        render_piece_in_hold(piece, render_type, context, graphics);
+    }
+}
+
+fn render_board(board: &Board, phase: Phase, context: Context, graphics: &mut G2d) {
+    let grid = Grid {
+        cols: WIDTH_IN_BLOCKS as u32,
+        rows: HEIGHT_IN_BLOCKS as u32,
+        units: BLOCK_SIZE
+    };
+    let line = Line::new(GREY, GRID_LINE_WIDTH);
+    let transform = context.transform.trans(GRID_X_OFFSET, GRID_Y_OFFSET);
+    grid.draw(&line, &Default::default(), transform, graphics);
+    let clearing = match phase {
+        Phase::LineClear(_) => true,
+        _ => false
+    };
+    for y in 0..HEIGHT_IN_BLOCKS {
+        // Completed rows flash white while they wait to be removed
+        if clearing && board.is_row_complete(y) {
+            let rect = RenderType::Normal.get_rectangle(color::WHITE);
+            for x in 0..WIDTH_IN_BLOCKS {
+                render_block(Block::new(x, y), get_grid_position(), rect, context, graphics);
+            }
+            continue;
+        }
+        for x in 0..WIDTH_IN_BLOCKS {
+            render_cell(board.get_cell_state(x, y), x, y, context, graphics);
+        }
+    }
+}
+
+fn render_cell(cell_state: CellState, x: i32, y: i32, context: Context, graphics: &mut G2d) {
+    match cell_state {
+        CellState::Block(shape) => {
+            let rect = RenderType::Normal.get_rectangle(shape_color(shape));
+            render_block(Block::new(x, y), get_grid_position(), rect, context, graphics);
+        },
+        CellState::Garbage => {
+            let rect = RenderType::Normal.get_rectangle(SILVER);
+            render_block(Block::new(x, y), get_grid_position(), rect, context, graphics);
+        },
+        CellState::Empty => {}
+    }
+}
+
+// Draws a piece on the board. Blocks up in the hidden rows above the playfield are left out.
+fn render_piece_in_grid(piece: &Piece, render_type: RenderType, context: Context,
+                        graphics: &mut G2d) {
+    let rect = render_type.get_rectangle(shape_color(piece.get_shape()));
+    for block in piece.blocks_iter().filter(|block| block.y >= 0) {
+        render_block(block, get_grid_position(), rect, context, graphics);
+    }
+}
+
+// Draws a piece from the preview queue, stacked down the right column in queue order.
+fn render_piece_in_next_piece(piece: &Piece, index: usize, context: Context,
+                              graphics: &mut G2d) {
+    let position = ScreenPosition::new(553.0, 80.0 + index as f64 * NEXT_PIECE_SPACING);
+    render_piece(piece, position, RenderType::Normal, context, graphics);
+}
+
+fn render_piece_in_hold(piece: &Piece, render_type: RenderType, context: Context,
+                        graphics: &mut G2d) {
+    let position = ScreenPosition::new(3.0, 80.0);
+    render_piece(piece, position, render_type, context, graphics);
+}
+
+fn render_piece(piece: &Piece, position: ScreenPosition, render_type: RenderType,
+                context: Context, graphics: &mut G2d) {
+    let rect = render_type.get_rectangle(shape_color(piece.get_shape()));
+    for block in piece.blocks_iter() {
+        render_block(block, position, rect, context, graphics);
+    }
+}
+
+fn render_block(block: Block, position: ScreenPosition, rect: Rectangle, context: Context,
+                graphics: &mut G2d) {
+    let square = rectangle::square(
+        position.x, position.y, BLOCK_SIZE - (2.0 * GRID_LINE_WIDTH)
+    );
+    let transform = context.transform.trans(
+        (block.x as f64) * BLOCK_SIZE,
+        (block.y as f64) * BLOCK_SIZE
+    );
+    rect.draw(square, &Default::default(), transform, graphics);
+}
+
+fn get_grid_position() -> ScreenPosition {
+    ScreenPosition::new(
+        GRID_X_OFFSET + GRID_LINE_WIDTH,
+        GRID_Y_OFFSET + GRID_LINE_WIDTH
+    )
+}
+
+pub enum RenderType {
+    Normal,
+    Ghost
+}
+impl RenderType {
+    pub fn get_rectangle(&self, color: Color) -> Rectangle {
+        match *self {
+            RenderType::Ghost => Rectangle {
+                color: color::BLACK,
+                shape: rectangle::Shape::Square,
+                border: Some(rectangle::Border {
+                    color: color,
+                    radius: GHOST_BORDER_WIDTH
+                })
+            },
+            RenderType::Normal => Rectangle {
+                color: color,
+                shape: rectangle::Shape::Square,
+                border: None
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ScreenPosition {
+    pub x: f64,
+    pub y: f64
+}
+impl ScreenPosition {
+    pub fn new(x: f64, y: f64) -> ScreenPosition {
+        ScreenPosition {x: x, y: y}
+    }
+}
@@ -0,0 +1,256 @@
+use std::cmp::min;
+
+use stats::TSpin;
+
+// What a scoring system gets to know about a piece locking.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lock {
+    pub lines: u32,
+    pub t_spin: TSpin,
+    // The level the piece locked on, counting from 1 (or from 0 in Master)
+    pub level: u32,
+    // Whether this is a difficult clear following another one
+    pub back_to_back: bool,
+    // Line clearing pieces in a row before this one, zero outside a combo
+    pub combo: u32,
+    // Lines cleared by those pieces
+    pub combo_lines: u32
+}
+
+// Turns what happens in a game into points. Games from different eras reward different things,
+// so scores are only comparable between games using the same system.
+pub trait Scoring {
+    // Points for a piece soft dropped `rows` rows.
+    fn soft_drop(&self, rows: u32) -> u32;
+    // Points for a piece hard dropped `rows` rows.
+    fn hard_drop(&self, rows: u32) -> u32;
+    // Points for a piece locking, whether or not it cleared any lines.
+    fn lock(&self, lock: &Lock) -> u32;
+    // Points on top of `lock` when the clear left the board empty.
+    fn perfect_clear(&self, lock: &Lock) -> u32;
+}
+
+// The scoring systems a game can be started with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScoringType {
+    // Modern guideline scoring with T-spins, back-to-backs, combos and perfect clears
+    Guideline,
+    // Nintendo's NES Tetris, counting line clears and soft drops only
+    Nes,
+    // Sega's 1988 arcade Tetris, counting line clears only
+    Sega,
+    // The arcade Master mode, where clears are worth more the higher the 0 to 999 level
+    Master
+}
+impl ScoringType {
+    pub fn create(&self) -> Box<Scoring> {
+        match *self {
+            ScoringType::Guideline => Box::new(GuidelineScoring),
+            ScoringType::Nes => Box::new(NesScoring),
+            ScoringType::Sega => Box::new(SegaScoring),
+            ScoringType::Master => Box::new(MasterScoring)
+        }
+    }
+}
+
+pub struct GuidelineScoring;
+impl Scoring for GuidelineScoring {
+    fn soft_drop(&self, rows: u32) -> u32 {
+        rows
+    }
+
+    fn hard_drop(&self, rows: u32) -> u32 {
+        2 * rows
+    }
+
+    // Tetrises and line clearing T-spins are worth half as much again when back-to-back, and
+    // every piece after the first in a combo adds a bonus.
+    fn lock(&self, lock: &Lock) -> u32 {
+        let mut score = match (lock.t_spin, lock.lines) {
+            (TSpin::None, 1) => 100,
+            (TSpin::None, 2) => 300,
+            (TSpin::None, 3) => 500,
+            (TSpin::None, 4) => 800,
+            (TSpin::Mini, 0) => 100,
+            (TSpin::Mini, 1) => 200,
+            (TSpin::Mini, 2) => 400,
+            (TSpin::Full, 0) => 400,
+            (TSpin::Full, 1) => 800,
+            (TSpin::Full, 2) => 1200,
+            (TSpin::Full, 3) => 1600,
+            _ => 0
+        } * lock.level;
+        if lock.back_to_back {
+            score = score * 3 / 2;
+        }
+        if lock.lines > 0 {
+            score += 50 * lock.combo * lock.level;
+        }
+        score
+    }
+
+    fn perfect_clear(&self, lock: &Lock) -> u32 {
+        let bonus = match lock.lines {
+            1 => 800,
+            2 => 1200,
+            3 => 1800,
+            4 if lock.back_to_back => 3200,
+            4 => 2000,
+            _ => 0
+        };
+        bonus * lock.level
+    }
+}
+
+// NES Tetris counts levels from 0, so its multiplier of level + 1 is our level.
+pub struct NesScoring;
+impl Scoring for NesScoring {
+    fn soft_drop(&self, rows: u32) -> u32 {
+        rows
+    }
+
+    fn hard_drop(&self, _rows: u32) -> u32 {
+        0
+    }
+
+    fn lock(&self, lock: &Lock) -> u32 {
+        let score = match lock.lines {
+            1 => 40,
+            2 => 100,
+            3 => 300,
+            4 => 1200,
+            _ => 0
+        };
+        score * lock.level
+    }
+
+    fn perfect_clear(&self, _lock: &Lock) -> u32 {
+        0
+    }
+}
+
+// Sega Tetris also counts levels from 0, with the multiplier going up every two levels until it
+// reaches 5.
+pub struct SegaScoring;
+impl Scoring for SegaScoring {
+    fn soft_drop(&self, _rows: u32) -> u32 {
+        0
+    }
+
+    fn hard_drop(&self, _rows: u32) -> u32 {
+        0
+    }
+
+    fn lock(&self, lock: &Lock) -> u32 {
+        let multiplier = min((lock.level - 1) / 2 + 1, 5);
+        let score = match lock.lines {
+            1 => 100,
+            2 => 400,
+            3 => 900,
+            4 => 2000,
+            _ => 0
+        };
+        score * multiplier
+    }
+
+    fn perfect_clear(&self, _lock: &Lock) -> u32 {
+        0
+    }
+}
+
+// Master scores a clear as the level after it divided by 4, rounded up, times the lines cleared
+// and the combo multiplier. The multiplier starts at 1 and every clear in the combo adds 2 for
+// each line past the first, so a Tetris on its own is worth 7 times over. A clear that empties
+// the board is worth four times as much again. Unlike the arcade, soft drops are added on rather
+// than multiplied in.
+pub struct MasterScoring;
+impl Scoring for MasterScoring {
+    fn soft_drop(&self, rows: u32) -> u32 {
+        rows
+    }
+
+    fn hard_drop(&self, _rows: u32) -> u32 {
+        0
+    }
+
+    fn lock(&self, lock: &Lock) -> u32 {
+        if lock.lines == 0 {
+            return 0;
+        }
+        let combo = 1 + 2 * (lock.combo_lines + lock.lines) - 2 * (lock.combo + 1);
+        (lock.level + lock.lines + 3) / 4 * lock.lines * combo
+    }
+
+    fn perfect_clear(&self, lock: &Lock) -> u32 {
+        3 * self.lock(lock)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stats::TSpin;
+
+    fn lock(lines: u32, level: u32) -> Lock {
+        Lock {
+            lines: lines,
+            t_spin: TSpin::None,
+            level: level,
+            back_to_back: false,
+            combo: 0,
+            combo_lines: 0
+        }
+    }
+
+    #[test]
+    fn test_guideline_scoring() {
+        let scoring = ScoringType::Guideline.create();
+        assert_eq!(scoring.lock(&lock(4, 2)), 1600);
+        let back_to_back = Lock { back_to_back: true, ..lock(4, 1) };
+        assert_eq!(scoring.lock(&back_to_back), 1200);
+        let combo = Lock { combo: 2, ..lock(1, 1) };
+        assert_eq!(scoring.lock(&combo), 200);
+        assert_eq!(scoring.perfect_clear(&back_to_back), 3200);
+        assert_eq!(scoring.hard_drop(10), 20);
+    }
+
+    #[test]
+    fn test_nes_scoring() {
+        let scoring = ScoringType::Nes.create();
+        assert_eq!(scoring.lock(&lock(1, 1)), 40);
+        assert_eq!(scoring.lock(&lock(4, 1)), 1200);
+        assert_eq!(scoring.lock(&lock(2, 10)), 1000);
+        let t_spin = Lock { t_spin: TSpin::Full, ..lock(0, 1) };
+        assert_eq!(scoring.lock(&t_spin), 0);
+        assert_eq!(scoring.soft_drop(5), 5);
+        assert_eq!(scoring.hard_drop(10), 0);
+        assert_eq!(scoring.perfect_clear(&lock(4, 1)), 0);
+    }
+
+    #[test]
+    fn test_sega_scoring() {
+        let scoring = ScoringType::Sega.create();
+        assert_eq!(scoring.lock(&lock(1, 1)), 100);
+        assert_eq!(scoring.lock(&lock(3, 2)), 900);
+        assert_eq!(scoring.lock(&lock(4, 3)), 4000);
+        assert_eq!(scoring.lock(&lock(4, 10)), 10000);
+        assert_eq!(scoring.soft_drop(5), 0);
+    }
+
+    #[test]
+    fn test_master_scoring() {
+        let scoring = ScoringType::Master.create();
+        assert_eq!(scoring.lock(&lock(0, 50)), 0);
+        assert_eq!(scoring.lock(&lock(1, 0)), 1);
+        assert_eq!(scoring.lock(&lock(4, 100)), 728);
+        assert_eq!(scoring.lock(&lock(2, 501)), 756);
+        let singles = Lock { combo: 3, combo_lines: 3, ..lock(1, 20) };
+        assert_eq!(scoring.lock(&singles), 6);
+        let combo = Lock { combo: 1, combo_lines: 2, ..lock(4, 100) };
+        assert_eq!(scoring.lock(&combo), 936);
+        assert_eq!(scoring.perfect_clear(&lock(4, 100)), 2184);
+        assert_eq!(scoring.soft_drop(5), 5);
+        assert_eq!(scoring.hard_drop(10), 0);
+    }
+}
@@ -1,15 +1,95 @@
+use gravity::GravityCurve;
+use leveling::LevelProgression;
+use mode::GameMode;
+use randomizer::RandomizerType;
+use scoring::ScoringType;
+
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
-pub const WINDOW_WIDTH: u32 = 800;
-pub const WINDOW_HEIGHT: u32 = 600;
 pub const HEIGHT_IN_BLOCKS: i32 = 20;
 pub const WIDTH_IN_BLOCKS: i32 = 10;
-pub const BLOCK_SIZE: f64 = 27.0;
-pub const GRID_LINE_WIDTH: f64 = 1.0;
-pub const GRID_X_OFFSET: f64 = (
-    WINDOW_WIDTH as f64 / 2.0 - (WIDTH_IN_BLOCKS as f64/2.0 * BLOCK_SIZE)
-);
-pub const GRID_Y_OFFSET: f64 = 25.0;
-pub const GHOST_BORDER_WIDTH: f64 = 0.3;
-pub const MAX_GAME_LEVEL: u32 = 10;
-pub const NEXT_PIECE_WIDTH: f64 = 150.0;
-pub const NEXT_PIECE_HEIGHT: f64 = 150.0;
+pub const MAX_GAME_LEVEL: u32 = 30;
+pub const DEFAULT_LINES_PER_LEVEL: u32 = 10;
+pub const DEFAULT_PREVIEW_COUNT: usize = 5;
+pub const MAX_PREVIEW_COUNT: usize = 6;
+pub const DEFAULT_LOCK_DELAY: f64 = 0.5;
+pub const DEFAULT_MAX_LOCK_RESETS: u32 = 15;
+pub const DEFAULT_DAS: f64 = 10.0 / 60.0;
+pub const DEFAULT_ARR: f64 = 2.0 / 60.0;
+pub const DEFAULT_SOFT_DROP_FACTOR: f64 = 20.0;
+pub const DEFAULT_HIDDEN_ROWS: u32 = 20;
+pub const MAX_HIDDEN_ROWS: u32 = 20;
+pub const DEFAULT_ARE: f64 = 0.0;
+pub const DEFAULT_LINE_CLEAR_DELAY: f64 = 0.0;
+pub const SPAWN_ROWS_ABOVE_PLAYFIELD: i32 = 2;
+// A T-spin that needed this kick (the last one in the table) always counts as a full T-spin.
+pub const T_SPIN_UPGRADE_KICK: usize = 4;
+// Seconds a line clear or T-spin stays announced on screen.
+pub const CALLOUT_DURATION: f64 = 2.0;
+// Seconds counted down before a race against the clock starts.
+pub const COUNTDOWN_DURATION: f64 = 3.0;
+pub const DEFAULT_TIME_LIMIT: f64 = 120.0;
+
+// Options chosen when a game is created. A reset game keeps the same settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GameSettings {
+    // How many upcoming pieces are shown, between 1 and MAX_PREVIEW_COUNT.
+    pub preview_count: usize,
+    pub randomizer: RandomizerType,
+    // Seed for the randomizer. Each game picks its own seed when this isn't set.
+    pub seed: Option<u32>,
+    // Seconds a piece can rest on the ground before it locks.
+    pub lock_delay: f64,
+    // How many moves or rotations on the ground can restart the lock delay. A piece gets a fresh
+    // allowance each time it reaches a lower row.
+    pub max_lock_resets: u32,
+    // Seconds a sideways move has to be held before it starts repeating (Delayed Auto Shift).
+    pub das: f64,
+    // Seconds between repeated sideways moves once DAS has charged (Auto Repeat Rate). Zero
+    // moves the piece straight to the wall.
+    pub arr: f64,
+    // How many times faster than gravity a piece falls while soft drop is held.
+    pub soft_drop_factor: f64,
+    // Seconds between a piece locking and the next one appearing (ARE, or entry delay).
+    pub are: f64,
+    // Seconds completed rows stay on the board before they are removed.
+    pub line_clear_delay: f64,
+    // Rows of buffer above the visible playfield, up to MAX_HIDDEN_ROWS. Pieces spawn up here and
+    // can be rotated or stacked into it without ending the game.
+    pub hidden_rows: u32,
+    // End the game when a piece locks with any block above the visible playfield, not only when
+    // the whole piece does.
+    pub partial_lock_out: bool,
+    pub scoring: ScoringType,
+    // How fast pieces fall as the level goes up.
+    pub gravity: GravityCurve,
+    // The level a game starts on, between 1 and MAX_GAME_LEVEL.
+    pub start_level: u32,
+    pub level_progression: LevelProgression,
+    pub mode: GameMode,
+    // Seconds of play in modes with a time limit.
+    pub time_limit: f64
+}
+impl GameSettings {
+    pub fn new() -> GameSettings {
+        GameSettings {
+            preview_count: DEFAULT_PREVIEW_COUNT,
+            randomizer: RandomizerType::History,
+            seed: None,
+            lock_delay: DEFAULT_LOCK_DELAY,
+            max_lock_resets: DEFAULT_MAX_LOCK_RESETS,
+            das: DEFAULT_DAS,
+            arr: DEFAULT_ARR,
+            soft_drop_factor: DEFAULT_SOFT_DROP_FACTOR,
+            are: DEFAULT_ARE,
+            line_clear_delay: DEFAULT_LINE_CLEAR_DELAY,
+            hidden_rows: DEFAULT_HIDDEN_ROWS,
+            partial_lock_out: false,
+            scoring: ScoringType::Guideline,
+            gravity: GravityCurve::Guideline,
+            start_level: 1,
+            level_progression: LevelProgression::Fixed(DEFAULT_LINES_PER_LEVEL),
+            mode: GameMode::Endless,
+            time_limit: DEFAULT_TIME_LIMIT
+        }
+    }
+}
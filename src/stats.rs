@@ -1,39 +1,150 @@
-use std::cmp::min;
+use std::cmp::{max, min};
 
-use settings::MAX_GAME_LEVEL;
-
-const LEVEL_THRESHOLD: u32 = 10;
+use leveling::LevelProgression;
+use settings::{GameSettings, MAX_GAME_LEVEL};
+use scoring::{Lock, Scoring};
 
 pub struct GameStats {
+    scoring: Box<Scoring>,
+    progression: LevelProgression,
+    start_level: u32,
     score: u32,
     lines: u32,
+    level: u32,
+    // Lines counted towards the current level's goal, as the progression awards them
+    level_lines: u32,
+    // Difficult clears in a row after the first, while the chain is alive
+    back_to_back: Option<u32>,
+    // Line clearing pieces in a row after the first, while the combo is alive
+    combo: Option<u32>,
+    // Lines cleared by the pieces in the combo so far
+    combo_lines: u32,
+    perfect_clears: u32,
+    // Lines cleared that held garbage
+    garbage_cleared: u32,
+    // Seconds of play, not counting pauses or the countdown
+    time: f64,
+    pieces: u32,
+    key_presses: u32,
+    // The last lock that cleared lines, for scoring a perfect clear on top of it
+    last_lock: Option<Lock>,
+    // Where each section ended, in progressions split into sections
+    splits: Vec<Split>
 }
 impl GameStats {
     pub fn new() -> GameStats {
+        Self::with_settings(GameSettings::new())
+    }
+
+    pub fn with_settings(settings: GameSettings) -> GameStats {
+        let start_level = max(1, min(settings.start_level, MAX_GAME_LEVEL));
         GameStats {
+            scoring: settings.scoring.create(),
+            progression: settings.level_progression,
+            start_level: start_level,
             score: 0,
-            lines: 0
+            lines: 0,
+            level: settings.level_progression.get_first_level(start_level),
+            level_lines: 0,
+            back_to_back: None,
+            combo: None,
+            combo_lines: 0,
+            perfect_clears: 0,
+            garbage_cleared: 0,
+            time: 0.0,
+            pieces: 0,
+            key_presses: 0,
+            last_lock: None,
+            splits: Vec::new()
         }
     }
 
+    pub fn add_time(&mut self, dt: f64) {
+        self.time += dt;
+    }
+
+    // Counts a piece locking, which moves the level on in progressions that count pieces.
+    pub fn count_piece(&mut self) {
+        self.pieces += 1;
+        let levels = self.progression.levels_per_piece(self.level);
+        self.advance_level(levels);
+    }
+
+    pub fn count_key_press(&mut self) {
+        self.key_presses += 1;
+    }
+
+    pub fn count_garbage_cleared(&mut self, lines: u32) {
+        self.garbage_cleared += lines;
+    }
+
     pub fn score_soft_drop(&mut self) {
-        self.score += 1;
+        self.score += self.scoring.soft_drop(1);
     }
 
     pub fn score_hard_drop(&mut self, rows_dropped: u32) {
-        self.score += 2 * rows_dropped;
+        self.score += self.scoring.hard_drop(rows_dropped);
     }
 
-    pub fn score_completed_lines(&mut self, lines: u32) {
-        self.score += match lines {
-            1 => 100,
-            2 => 300,
-            3 => 500,
-            4 => 800,
-            _ => 0
-        } * self.get_level();
+    // Scores a piece locking, whether or not it cleared any lines. Tetrises and line clearing
+    // T-spins are difficult clears, which keep a back-to-back chain going; any other clear breaks
+    // it. Every piece in a row that clears lines extends the combo.
+    pub fn score_completed_lines(&mut self, lines: u32, t_spin: TSpin) {
+        let mut back_to_back = false;
+        if lines > 0 {
+            let difficult = lines == 4 || t_spin != TSpin::None;
+            if difficult {
+                back_to_back = self.back_to_back.is_some();
+                self.back_to_back = Some(self.back_to_back.map_or(0, |chain| chain + 1));
+            } else {
+                self.back_to_back = None;
+            }
+            self.combo = Some(self.combo.map_or(0, |combo| combo + 1));
+        } else {
+            self.combo = None;
+            self.combo_lines = 0;
+        }
 
+        let lock = Lock {
+            lines: lines,
+            t_spin: t_spin,
+            level: self.get_level(),
+            back_to_back: back_to_back,
+            combo: self.get_combo(),
+            combo_lines: self.combo_lines
+        };
+        self.score += self.scoring.lock(&lock);
+        self.combo_lines += lines;
         self.lines += lines;
+        self.last_lock = Some(lock);
+        let awarded = self.progression.awarded_lines(lines, t_spin, back_to_back);
+        self.advance_level(awarded);
+    }
+
+    fn advance_level(&mut self, awarded_lines: u32) {
+        self.level_lines += awarded_lines;
+        while self.level < self.progression.get_max_level() &&
+              self.level_lines >= self.get_level_goal() {
+            self.level_lines -= self.get_level_goal();
+            self.level += 1;
+        }
+        if let Some(sections) = self.progression.get_sections_cleared(self.level) {
+            while (self.splits.len() as u32) < sections {
+                self.splits.push(Split { time: self.time, score: self.score });
+            }
+        }
+    }
+
+    fn get_level_goal(&self) -> u32 {
+        self.progression.goal(self.level, self.start_level)
+    }
+
+    // Scores the last lock leaving the board empty, on top of the score for the clear itself.
+    pub fn score_perfect_clear(&mut self) {
+        if let Some(lock) = self.last_lock {
+            self.score += self.scoring.perfect_clear(&lock);
+            self.perfect_clears += 1;
+        }
     }
 
     pub fn get_score(&self) -> u32 {
@@ -44,41 +155,258 @@ impl GameStats {
         self.lines
     }
 
+    // How many difficult clears have followed the first one in the current back-to-back chain.
+    pub fn get_back_to_back(&self) -> u32 {
+        self.back_to_back.unwrap_or(0)
+    }
+
+    // How many line clearing pieces have followed the first one in the current combo.
+    pub fn get_combo(&self) -> u32 {
+        self.combo.unwrap_or(0)
+    }
+
+    pub fn get_perfect_clears(&self) -> u32 {
+        self.perfect_clears
+    }
+
     pub fn get_level(&self) -> u32 {
-        let level = (self.lines / LEVEL_THRESHOLD) + 1;
-        min(level, MAX_GAME_LEVEL)
+        self.level
+    }
+
+    pub fn get_garbage_cleared(&self) -> u32 {
+        self.garbage_cleared
+    }
+
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+
+    // How many pieces have locked.
+    pub fn get_pieces(&self) -> u32 {
+        self.pieces
+    }
+
+    pub fn get_key_presses(&self) -> u32 {
+        self.key_presses
+    }
+
+    pub fn get_pieces_per_second(&self) -> f64 {
+        if self.time > 0.0 {
+            self.pieces as f64 / self.time
+        } else {
+            0.0
+        }
+    }
+
+    pub fn get_keys_per_piece(&self) -> f64 {
+        if self.pieces > 0 {
+            self.key_presses as f64 / self.pieces as f64
+        } else {
+            0.0
+        }
+    }
+
+    // Where each section played through so far ended.
+    pub fn get_splits(&self) -> &[Split] {
+        &self.splits
+    }
+
+    // How long each section played through so far took.
+    pub fn get_section_times(&self) -> Vec<f64> {
+        let mut section_start = 0.0;
+        self.splits.iter().map(|split| {
+            let section_time = split.time - section_start;
+            section_start = split.time;
+            section_time
+        }).collect()
+    }
+
+    // How many more lines, as the level progression counts them, it takes to reach the next
+    // level. Zero once the top level is reached.
+    pub fn get_lines_to_next_level(&self) -> u32 {
+        if self.level >= self.progression.get_max_level() {
+            0
+        } else {
+            self.get_level_goal() - self.level_lines
+        }
+    }
+}
+
+#[cfg(test)]
+impl GameStats {
+    pub fn set_level(&mut self, level: u32) {
+        self.level = level;
+    }
+
+    pub fn set_lines(&mut self, lines: u32) {
+        self.lines = lines;
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TSpin {
+    None,
+    Mini,
+    Full
+}
+
+// The game clock and score at the end of a section.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Split {
+    pub time: f64,
+    pub score: u32
+}
+
+// What a piece achieved when it locked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineClear {
+    pub lines: u32,
+    pub t_spin: TSpin,
+    // Whether the clear left the board empty
+    pub perfect_clear: bool
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use settings::MAX_GAME_LEVEL;
+    use leveling::{LevelProgression, MASTER_MAX_LEVEL};
+    use settings::{GameSettings, MAX_GAME_LEVEL};
+    use scoring::ScoringType;
 
     #[test]
     fn test_score_completed_lines() {
         let mut stats = GameStats::new();
-        stats.score_completed_lines(1);
+        stats.score_completed_lines(1, TSpin::None);
         assert_eq!(stats.get_score(), 100);
         assert_eq!(stats.get_level(), 1);
         assert_eq!(stats.get_lines(), 1);
 
-        stats.score_completed_lines(2);
+        stats.score_completed_lines(0, TSpin::None);
+        stats.score_completed_lines(2, TSpin::None);
         assert_eq!(stats.get_score(), 400);
         assert_eq!(stats.get_level(), 1);
         assert_eq!(stats.get_lines(), 3);
 
-        stats.score_completed_lines(3);
+        stats.score_completed_lines(0, TSpin::None);
+        stats.score_completed_lines(3, TSpin::None);
         assert_eq!(stats.get_score(), 900);
         assert_eq!(stats.get_level(), 1);
         assert_eq!(stats.get_lines(), 6);
 
-        stats.score_completed_lines(4);
+        stats.score_completed_lines(0, TSpin::None);
+        stats.score_completed_lines(4, TSpin::None);
         assert_eq!(stats.get_score(), 1700);
         assert_eq!(stats.get_level(), 2);
         assert_eq!(stats.get_lines(), 10);
     }
 
+    fn score_of(lines: u32, t_spin: TSpin) -> u32 {
+        let mut stats = GameStats::new();
+        stats.score_completed_lines(lines, t_spin);
+        stats.get_score()
+    }
+
+    #[test]
+    fn test_score_t_spins() {
+        assert_eq!(score_of(0, TSpin::Mini), 100);
+        assert_eq!(score_of(1, TSpin::Mini), 200);
+        assert_eq!(score_of(2, TSpin::Mini), 400);
+        assert_eq!(score_of(0, TSpin::Full), 400);
+        assert_eq!(score_of(1, TSpin::Full), 800);
+        assert_eq!(score_of(2, TSpin::Full), 1200);
+        assert_eq!(score_of(3, TSpin::Full), 1600);
+    }
+
+    #[test]
+    fn test_back_to_back() {
+        let mut stats = GameStats::new();
+        stats.score_completed_lines(4, TSpin::None);
+        stats.score_completed_lines(0, TSpin::None);
+        assert_eq!(stats.get_score(), 800);
+        assert_eq!(stats.get_back_to_back(), 0);
+        stats.score_completed_lines(4, TSpin::None);
+        stats.score_completed_lines(0, TSpin::None);
+        assert_eq!(stats.get_score(), 2000);
+        assert_eq!(stats.get_back_to_back(), 1);
+        stats.score_completed_lines(0, TSpin::Full);
+        stats.score_completed_lines(2, TSpin::Full);
+        stats.score_completed_lines(0, TSpin::None);
+        assert_eq!(stats.get_score(), 2000 + 400 + 1800);
+        assert_eq!(stats.get_back_to_back(), 2);
+        stats.score_completed_lines(1, TSpin::None);
+        assert_eq!(stats.get_back_to_back(), 0);
+        stats.score_completed_lines(0, TSpin::None);
+        stats.score_completed_lines(4, TSpin::None);
+        assert_eq!(stats.get_score(), 4200 + 200 + 1600);
+    }
+
+    #[test]
+    fn test_combo() {
+        let mut stats = GameStats::new();
+        stats.score_completed_lines(1, TSpin::None);
+        assert_eq!(stats.get_combo(), 0);
+        assert_eq!(stats.get_score(), 100);
+        stats.score_completed_lines(1, TSpin::None);
+        assert_eq!(stats.get_combo(), 1);
+        assert_eq!(stats.get_score(), 100 + 150);
+        stats.score_completed_lines(2, TSpin::None);
+        assert_eq!(stats.get_combo(), 2);
+        assert_eq!(stats.get_score(), 250 + 400);
+        stats.score_completed_lines(0, TSpin::None);
+        assert_eq!(stats.get_combo(), 0);
+        stats.score_completed_lines(1, TSpin::None);
+        assert_eq!(stats.get_score(), 750);
+    }
+
+    #[test]
+    fn test_score_perfect_clear() {
+        let mut stats = GameStats::new();
+        stats.score_completed_lines(2, TSpin::None);
+        stats.score_perfect_clear();
+        assert_eq!(stats.get_score(), 300 + 1200);
+        assert_eq!(stats.get_perfect_clears(), 1);
+
+        let mut stats = GameStats::new();
+        stats.score_completed_lines(4, TSpin::None);
+        stats.score_perfect_clear();
+        assert_eq!(stats.get_score(), 800 + 2000);
+        stats.score_completed_lines(0, TSpin::None);
+        stats.score_completed_lines(4, TSpin::None);
+        stats.score_perfect_clear();
+        assert_eq!(stats.get_score(), 2800 + 1200 + 3200);
+        assert_eq!(stats.get_perfect_clears(), 2);
+    }
+
+    #[test]
+    fn test_with_scoring() {
+        let settings = GameSettings { scoring: ScoringType::Nes, ..GameSettings::new() };
+        let mut stats = GameStats::with_settings(settings);
+        stats.score_completed_lines(4, TSpin::None);
+        stats.score_completed_lines(4, TSpin::None);
+        stats.score_perfect_clear();
+        stats.score_hard_drop(10);
+        assert_eq!(stats.get_score(), 2400);
+        assert_eq!(stats.get_back_to_back(), 1);
+    }
+
+    #[test]
+    fn test_pace() {
+        let mut stats = GameStats::new();
+        assert_eq!(stats.get_pieces_per_second(), 0.0);
+        assert_eq!(stats.get_keys_per_piece(), 0.0);
+        for _ in 0..10 {
+            stats.count_piece();
+            stats.count_key_press();
+            stats.count_key_press();
+            stats.count_key_press();
+        }
+        stats.add_time(2.5);
+        stats.add_time(2.5);
+        assert_eq!(stats.get_pieces(), 10);
+        assert_eq!(stats.get_pieces_per_second(), 2.0);
+        assert_eq!(stats.get_keys_per_piece(), 3.0);
+    }
+
     #[test]
     fn test_score_soft_drop() {
         let mut stats = GameStats::new();
@@ -101,10 +429,93 @@ mod tests {
     fn test_get_level() {
         let mut stats = GameStats::new();
         assert_eq!(stats.get_level(), 1);
-        stats.lines = 89;
+        assert_eq!(stats.get_lines_to_next_level(), 10);
+        clear_singles(&mut stats, 89);
         assert_eq!(stats.get_level(), 9);
-        stats.lines = 150;
+        assert_eq!(stats.get_lines_to_next_level(), 1);
+        clear_singles(&mut stats, 61);
+        assert_eq!(stats.get_level(), 16);
+        clear_singles(&mut stats, 200);
         assert_eq!(stats.get_level(), MAX_GAME_LEVEL);
+        assert_eq!(stats.get_lines_to_next_level(), 0);
     }
-}
 
+    fn clear_singles(stats: &mut GameStats, count: u32) {
+        for _ in 0..count {
+            stats.score_completed_lines(1, TSpin::None);
+            stats.score_completed_lines(0, TSpin::None);
+        }
+    }
+
+    #[test]
+    fn test_start_level() {
+        let settings = GameSettings { start_level: 5, ..GameSettings::new() };
+        let mut stats = GameStats::with_settings(settings);
+        assert_eq!(stats.get_level(), 5);
+        clear_singles(&mut stats, 10);
+        assert_eq!(stats.get_level(), 6);
+        assert_eq!(stats.get_lines(), 10);
+    }
+
+    #[test]
+    fn test_variable_goal() {
+        let settings = GameSettings {
+            level_progression: LevelProgression::Variable,
+            ..GameSettings::new()
+        };
+        let mut stats = GameStats::with_settings(settings);
+        assert_eq!(stats.get_lines_to_next_level(), 5);
+        stats.score_completed_lines(4, TSpin::None);
+        assert_eq!(stats.get_level(), 2);
+        assert_eq!(stats.get_lines_to_next_level(), 7);
+    }
+
+    #[test]
+    fn test_nes_progression() {
+        let settings = GameSettings {
+            level_progression: LevelProgression::Nes,
+            start_level: 10,
+            ..GameSettings::new()
+        };
+        let mut stats = GameStats::with_settings(settings);
+        clear_singles(&mut stats, 99);
+        assert_eq!(stats.get_level(), 10);
+        clear_singles(&mut stats, 1);
+        assert_eq!(stats.get_level(), 11);
+        assert_eq!(stats.get_lines_to_next_level(), 10);
+    }
+
+    #[test]
+    fn test_master_progression() {
+        let settings = GameSettings {
+            level_progression: LevelProgression::Master,
+            scoring: ScoringType::Master,
+            ..GameSettings::new()
+        };
+        let mut stats = GameStats::with_settings(settings);
+        assert_eq!(stats.get_level(), 0);
+        for _ in 0..120 {
+            stats.count_piece();
+        }
+        assert_eq!(stats.get_level(), 99);
+        assert!(stats.get_splits().is_empty());
+        stats.add_time(30.0);
+        stats.score_completed_lines(2, TSpin::None);
+        assert_eq!(stats.get_level(), 101);
+        assert_eq!(stats.get_score(), 156);
+        assert_eq!(stats.get_splits(), &[Split { time: 30.0, score: 156 }]);
+        stats.set_level(997);
+        stats.count_piece();
+        stats.count_piece();
+        assert_eq!(stats.get_level(), 998);
+        stats.add_time(70.0);
+        stats.score_completed_lines(4, TSpin::None);
+        assert_eq!(stats.get_level(), MASTER_MAX_LEVEL);
+        assert_eq!(stats.get_lines_to_next_level(), 0);
+        let section_times = stats.get_section_times();
+        assert_eq!(section_times.len(), 10);
+        assert_eq!(section_times[0], 30.0);
+        assert_eq!(section_times[8], 0.0);
+        assert_eq!(section_times[9], 70.0);
+    }
+}
@@ -1,8 +1,3 @@
-use piston_window::{Context, G2d, Rectangle, Transformed, color, rectangle};
-use piston_window::types::Color;
-
-use colors::*;
-use game::ScreenPosition;
 use settings::*;
 
 
@@ -13,7 +8,8 @@ pub static I: Tetromino = Tetromino {
         [Block{x:0, y:2}, Block{x:1, y:2}, Block{x:2, y:2}, Block{x:3, y:2}],
         [Block{x:1, y:0}, Block{x:1, y:1}, Block{x:1, y:2}, Block{x:1, y:3}]
     ],
-    color: CYAN
+    shape: Shape::I,
+    kicks: &I_KICKS
 };
 
 pub static J: Tetromino = Tetromino {
@@ -23,7 +19,8 @@ pub static J: Tetromino = Tetromino {
         [Block{x:2, y:2}, Block{x:2, y:1}, Block{x:1, y:1}, Block{x:0, y:1}],
         [Block{x:0, y:2}, Block{x:1, y:2}, Block{x:1, y:1}, Block{x:1, y:0}]
     ],
-    color: BLUE
+    shape: Shape::J,
+    kicks: &JLSTZ_KICKS
 };
 
 pub static L: Tetromino = Tetromino {
@@ -33,7 +30,8 @@ pub static L: Tetromino = Tetromino {
         [Block{x:0, y:2}, Block{x:0, y:1}, Block{x:1, y:1}, Block{x:2, y:1}],
         [Block{x:0, y:0}, Block{x:1, y:0}, Block{x:1, y:1}, Block{x:1, y:2}]
     ],
-    color: ORANGE
+    shape: Shape::L,
+    kicks: &JLSTZ_KICKS
 };
 
 pub static O: Tetromino = Tetromino {
@@ -43,7 +41,8 @@ pub static O: Tetromino = Tetromino {
         [Block{x:1, y:0}, Block{x:1, y:1}, Block{x:2, y:0}, Block{x:2, y:1}],
         [Block{x:1, y:0}, Block{x:1, y:1}, Block{x:2, y:0}, Block{x:2, y:1}]
     ],
-    color: YELLOW
+    shape: Shape::O,
+    kicks: &O_KICKS
 };
 
 pub static S: Tetromino = Tetromino {
@@ -53,7 +52,8 @@ pub static S: Tetromino = Tetromino {
         [Block{x:2, y:1}, Block{x:1, y:1}, Block{x:1, y:2}, Block{x:0, y:2}],
         [Block{x:1, y:2}, Block{x:1, y:1}, Block{x:0, y:1}, Block{x:0, y:0}]
     ],
-    color: LIME
+    shape: Shape::S,
+    kicks: &JLSTZ_KICKS
 };
 
 pub static T: Tetromino = Tetromino {
@@ -63,7 +63,8 @@ pub static T: Tetromino = Tetromino {
         [Block{x:1, y:2}, Block{x:2, y:1}, Block{x:1, y:1}, Block{x:0, y:1}],
         [Block{x:0, y:1}, Block{x:1, y:0}, Block{x:1, y:1}, Block{x:1, y:2}]
     ],
-    color: PURPLE
+    shape: Shape::T,
+    kicks: &JLSTZ_KICKS
 };
 
 pub static Z: Tetromino = Tetromino {
@@ -73,11 +74,56 @@ pub static Z: Tetromino = Tetromino {
         [Block{x:2, y:2}, Block{x:1, y:2}, Block{x:1, y:1}, Block{x:0, y:1}],
         [Block{x:0, y:2}, Block{x:0, y:1}, Block{x:1, y:1}, Block{x:1, y:0}]
     ],
-    color: RED
+    shape: Shape::Z,
+    kicks: &JLSTZ_KICKS
+};
+
+// Super Rotation System wall kicks, indexed by the rotation state being rotated out of. Each
+// entry is the list of (x, y) offsets to try, in order. The y axis points down the board, so
+// these are the guideline tables with y negated. Counter-clockwise kicks are not listed since
+// they are the clockwise kicks of the reverse rotation, negated.
+static JLSTZ_KICKS: KickTable = KickTable {
+    clockwise: [
+        &[(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+        &[(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+        &[(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+        &[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]
+    ],
+    half_turn: HALF_TURN_KICKS
+};
+
+static I_KICKS: KickTable = KickTable {
+    clockwise: [
+        &[(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+        &[(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+        &[(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+        &[(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)]
+    ],
+    half_turn: HALF_TURN_KICKS
 };
 
+static O_KICKS: KickTable = KickTable {
+    clockwise: [&[(0, 0)], &[(0, 0)], &[(0, 0)], &[(0, 0)]],
+    half_turn: [&[(0, 0)], &[(0, 0)], &[(0, 0)], &[(0, 0)]]
+};
+
+// 180 degree kicks aren't part of the guideline, these are the widely used SRS+ ones.
+const HALF_TURN_KICKS: [&'static [Kick]; 4] = [
+    &[(0, 0), (0, -1), (1, -1), (-1, -1), (1, 0), (-1, 0)],
+    &[(0, 0), (1, 0), (1, -2), (1, -1), (0, -2), (0, -1)],
+    &[(0, 0), (0, 1), (-1, 1), (1, 1), (-1, 0), (1, 0)],
+    &[(0, 0), (-1, 0), (-1, -2), (-1, -1), (0, -2), (0, -1)]
+];
+
 pub type Configuration = [Block; 4];
 type Rotation = usize;
+type Kick = (i32, i32);
+
+#[derive(Debug, PartialEq)]
+struct KickTable {
+    clockwise: [&'static [Kick]; 4],
+    half_turn: [&'static [Kick]; 4]
+}
 
 #[derive(Clone, Copy)]
 pub struct Piece {
@@ -92,6 +138,11 @@ impl Piece {
         Piece::new(x, 0, ptype, 0)
     }
 
+    // Returns the same kind of piece back in its spawn position and orientation.
+    pub fn respawned(&self) -> Piece {
+        Piece::create(self.ptype)
+    }
+
     fn new(x: i32, y: i32, ptype: &'static Tetromino, rotation: Rotation) -> Piece {
         Piece {
             x: x,
@@ -106,11 +157,23 @@ impl Piece {
         BlockIterator::new(self.x, self.y, configuration)
     }
 
-    pub fn rotated(&self) -> Self {
-        let new_rotation = (self.rotation + 1) % self.ptype.configurations.len();
-        let mut rotated = Self::new(self.x, self.y, self.ptype, new_rotation);
-        rotated.x -= rotated.wall_kick_translation();
-        rotated
+    pub fn rotated(&self, direction: RotationDirection) -> Self {
+        let turns = match direction {
+            RotationDirection::Clockwise => 1,
+            RotationDirection::Half => 2,
+            RotationDirection::CounterClockwise => 3
+        };
+        let new_rotation = (self.rotation + turns) % self.ptype.configurations.len();
+        Self::new(self.x, self.y, self.ptype, new_rotation)
+    }
+
+    // Returns the candidate positions for a rotation in the order the Super Rotation System
+    // tests them. The first one that fits on the board is the one to use.
+    pub fn kicked_rotations(&self, direction: RotationDirection) -> Vec<Piece> {
+        let rotated = self.rotated(direction);
+        self.ptype.get_kicks(self.rotation, direction).iter().map(|&(kick_x, kick_y)| {
+            Self::new(rotated.x + kick_x, rotated.y + kick_y, rotated.ptype, rotated.rotation)
+        }).collect()
     }
 
     pub fn moved(&self, direction: Direction) -> Self {
@@ -122,70 +185,13 @@ impl Piece {
         Self::new(self.x + trans_x, self.y + trans_y, self.ptype, self.rotation)
     }
 
-    fn wall_kick_translation(&self) -> i32 {
-        let min_block = self.blocks_iter().min_by_key(|block| block.x).unwrap();
-        let max_block = self.blocks_iter().max_by_key(|block| block.x).unwrap();
-        if min_block.x < 0 {
-            min_block.x
-        } else if max_block.x >= WIDTH_IN_BLOCKS {
-            max_block.x - WIDTH_IN_BLOCKS + 1
-        } else {
-            0
-        }
-    }
-
-    pub fn get_color(&self) -> Color {
-        self.ptype.color
-    }
-
-    pub fn render_in_grid(&self, render_type: RenderType, context: Context, graphics: &mut G2d) {
-        let position = get_grid_position();
-        self.render(position, render_type, context, graphics);
-    }
-
-    pub fn render_in_next_piece(&self, render_type: RenderType, context: Context,
-                                graphics: &mut G2d) {
-        let position = ScreenPosition::new(553.0, 80.0);
-        self.render(position, render_type, context, graphics);
+    // The rotation state, counting clockwise quarter turns from the spawn orientation.
+    pub fn get_rotation(&self) -> usize {
+        self.rotation
     }
 
-    fn render(&self, position: ScreenPosition, render_type: RenderType, context: Context,
-                  graphics: &mut G2d) {
-        let rect = render_type.get_rectangle(self.get_color());
-        for block in self.blocks_iter() {
-            block.render(position, rect, context, graphics);
-        }
-    }
-}
-
-fn get_grid_position() -> ScreenPosition {
-    ScreenPosition::new(
-        GRID_X_OFFSET + GRID_LINE_WIDTH,
-        GRID_Y_OFFSET + GRID_LINE_WIDTH
-    )
-}
-
-pub enum RenderType {
-    Normal,
-    Ghost
-}
-impl RenderType {
-    pub fn get_rectangle(&self, color: Color) -> Rectangle {
-        match *self {
-            RenderType::Ghost => Rectangle {
-                color: color::BLACK,
-                shape: rectangle::Shape::Square,
-                border: Some(rectangle::Border {
-                    color: color,
-                    radius: GHOST_BORDER_WIDTH
-                })
-            },
-            RenderType::Normal => Rectangle {
-                color: color,
-                shape: rectangle::Shape::Square,
-                border: None
-            }
-        }
+    pub fn get_shape(&self) -> Shape {
+        self.ptype.shape
     }
 }
 
@@ -219,21 +225,52 @@ impl Iterator for BlockIterator {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Direction {
     Left,
     Right,
     Down
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RotationDirection {
+    Clockwise,
+    CounterClockwise,
+    Half
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    I,
+    J,
+    L,
+    O,
+    S,
+    T,
+    Z
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Tetromino {
     configurations: [Configuration; 4],
-    color: Color
+    shape: Shape,
+    kicks: &'static KickTable
 }
 impl Tetromino {
     fn get_configuration(&self, rotation: Rotation) -> &Configuration {
         &self.configurations[rotation]
     }
+
+    fn get_kicks(&self, rotation: Rotation, direction: RotationDirection) -> Vec<Kick> {
+        match direction {
+            RotationDirection::Clockwise => self.kicks.clockwise[rotation].to_vec(),
+            RotationDirection::Half => self.kicks.half_turn[rotation].to_vec(),
+            RotationDirection::CounterClockwise => {
+                let reverse = (rotation + 3) % self.configurations.len();
+                self.kicks.clockwise[reverse].iter().map(|&(x, y)| (-x, -y)).collect()
+            }
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -245,22 +282,6 @@ impl Block {
     pub fn new(x: i32, y: i32) -> Block {
         Block {x: x, y: y}
     }
-
-    pub fn render_in_grid(&self, rect: Rectangle, context: Context, graphics: &mut G2d) {
-        self.render(get_grid_position(), rect, context, graphics);
-    }
-
-    pub fn render(&self, position: ScreenPosition, rect: Rectangle, context: Context,
-                  graphics: &mut G2d) {
-        let square = rectangle::square(
-            position.x, position.y, BLOCK_SIZE - (2.0 * GRID_LINE_WIDTH)
-        );
-        let transform = context.transform.trans(
-            (self.x as f64) * BLOCK_SIZE,
-            (self.y as f64) * BLOCK_SIZE
-        );
-        rect.draw(square, &Default::default(), transform, graphics);
-    }
 }
 
 
@@ -272,63 +293,90 @@ mod tests {
     fn test_piece_rotated() {
         let original = Piece::create(&I);
         assert_eq!(original.rotation, 0);
-        let mut rotated = original.rotated();
+        let mut rotated = original.rotated(RotationDirection::Clockwise);
         assert_eq!(rotated.rotation, 1);
         assert_eq!(original.x, rotated.x);
-        rotated = rotated.rotated();
+        rotated = rotated.rotated(RotationDirection::Clockwise);
         assert_eq!(rotated.rotation, 2);
         assert_eq!(original.x, rotated.x);
-        rotated = rotated.rotated();
+        rotated = rotated.rotated(RotationDirection::Clockwise);
         assert_eq!(rotated.rotation, 3);
         assert_eq!(original.x, rotated.x);
-        rotated = rotated.rotated();
+        rotated = rotated.rotated(RotationDirection::Clockwise);
         assert_eq!(rotated.rotation, 0);
         assert_eq!(original.x, rotated.x);
     }
 
     #[test]
-    fn test_piece_rotated_kicked() {
-        let mut p = Piece::create(&I);
-        p.x = 9;
-        let rotated = p.rotated();
-        assert_eq!(rotated.x, 7)
+    fn test_piece_rotated_counter_clockwise() {
+        let original = Piece::create(&T);
+        let mut rotated = original.rotated(RotationDirection::CounterClockwise);
+        assert_eq!(rotated.rotation, 3);
+        rotated = rotated.rotated(RotationDirection::CounterClockwise);
+        assert_eq!(rotated.rotation, 2);
+        rotated = rotated.rotated(RotationDirection::Half);
+        assert_eq!(rotated.rotation, 0);
+        assert_eq!(original.x, rotated.x);
     }
 
     #[test]
-    fn test_piece_moved_left() {
-        let p = Piece::create(&Z);
-        let result = p.moved(Direction::Left);
-        assert_eq!(result.x, p.x - 1);
+    fn test_piece_kicked_rotations() {
+        let p = Piece::create(&T);
+        let rotations = p.kicked_rotations(RotationDirection::Clockwise);
+        assert_eq!(rotations.len(), 5);
+        assert!(rotations.iter().all(|r| r.rotation == 1));
+        let offsets: Vec<(i32, i32)> = rotations.iter().map(|r| (r.x - p.x, r.y - p.y)).collect();
+        assert_eq!(offsets, vec![(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]);
     }
 
     #[test]
-    fn test_piece_moved_right() {
-        let p = Piece::create(&Z);
-        let result = p.moved(Direction::Right);
-        assert_eq!(result.x, p.x + 1);
+    fn test_piece_kicked_rotations_i() {
+        let p = Piece::create(&I).rotated(RotationDirection::Clockwise);
+        let rotations = p.kicked_rotations(RotationDirection::Clockwise);
+        assert!(rotations.iter().all(|r| r.rotation == 2));
+        let offsets: Vec<(i32, i32)> = rotations.iter().map(|r| (r.x - p.x, r.y - p.y)).collect();
+        assert_eq!(offsets, vec![(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)]);
+    }
+
+    #[test]
+    fn test_piece_kicked_rotations_counter_clockwise() {
+        let p = Piece::create(&T).rotated(RotationDirection::Clockwise);
+        let rotations = p.kicked_rotations(RotationDirection::CounterClockwise);
+        assert!(rotations.iter().all(|r| r.rotation == 0));
+        let offsets: Vec<(i32, i32)> = rotations.iter().map(|r| (r.x - p.x, r.y - p.y)).collect();
+        assert_eq!(offsets, vec![(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]);
+    }
+
+    #[test]
+    fn test_piece_kicked_rotations_half() {
+        let p = Piece::create(&S);
+        let rotations = p.kicked_rotations(RotationDirection::Half);
+        assert_eq!(rotations.len(), 6);
+        assert!(rotations.iter().all(|r| r.rotation == 2));
+        assert_eq!((rotations[1].x, rotations[1].y), (p.x, p.y - 1));
     }
 
     #[test]
-    fn test_wall_kick_in_bounds() {
-        let p = Piece::create(&I);
-        let kicked_translation = p.wall_kick_translation();
-        assert_eq!(kicked_translation, 0);
+    fn test_piece_kicked_rotations_o() {
+        let p = Piece::create(&O);
+        let rotations = p.kicked_rotations(RotationDirection::Clockwise);
+        assert_eq!(rotations.len(), 1);
+        assert_eq!(rotations[0].x, p.x);
+        assert_eq!(rotations[0].y, p.y);
     }
 
     #[test]
-    fn test_wall_kick_out_of_bounds_right() {
-        let mut p = Piece::create(&I);
-        p.x = 9;
-        let kicked_translation = p.wall_kick_translation();
-        assert_eq!(kicked_translation, 3);
+    fn test_piece_moved_left() {
+        let p = Piece::create(&Z);
+        let result = p.moved(Direction::Left);
+        assert_eq!(result.x, p.x - 1);
     }
 
     #[test]
-    fn test_wall_kick_out_of_bounds_left() {
-        let mut p = Piece::create(&I);
-        p.x = -2;
-        let kicked_translation = p.wall_kick_translation();
-        assert_eq!(kicked_translation, -2);
+    fn test_piece_moved_right() {
+        let p = Piece::create(&Z);
+        let result = p.moved(Direction::Right);
+        assert_eq!(result.x, p.x + 1);
     }
 
     #[test]
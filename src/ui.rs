@@ -1,13 +1,19 @@
 use find_folder;
 use conrod::{
-    Button, Canvas, Colorable, Frameable, Positionable, Labelable, Sizeable, Theme,
-    Ui, UiCell, Text, Widget, color
+    Button, Canvas, Colorable, Frameable, Positionable, Labelable, NumberDialer, Sizeable, Theme,
+    Ui, UiCell, Text, Widget, WidgetId, color
 };
 use piston_window::{G2d, Glyphs, Graphics, PistonWindow};
 
-use game::{Rustris, GameState};
-use stats::GameStats;
-use settings::*;
+use rustris::game::{Rustris, GameState, TopOut};
+use rustris::leveling::SECTION_LEVELS;
+use rustris::master::{get_grade, get_level_stop};
+use rustris::mode::GameMode;
+use rustris::settings::MAX_GAME_LEVEL;
+use rustris::stats::{GameStats, LineClear, TSpin};
+
+use layout::*;
+use records::{Records, LEADERBOARD_SIZE, record_name};
 
 
 pub type Backend = (<G2d<'static> as Graphics>::Texture, Glyphs);
@@ -24,19 +30,29 @@ pub fn create_ui(window: &PistonWindow) -> UI {
     UI::new(glyph_cache.unwrap(), theme)
 }
 
-pub fn set_ui(ref mut ui: UICell, game: &mut Rustris) {
+pub fn set_ui(ref mut ui: UICell, game: &mut Rustris, records: &Records) {
     Canvas::new().flow_right(&[
         (LEFT_COLUMN, Canvas::new().color(color::DARK_CHARCOAL).pad(20.0)),
         (MIDDLE_COLUMN, Canvas::new().color(color::TRANSPARENT).length(300.0)),
         (RIGHT_COLUMN, Canvas::new().color(color::DARK_CHARCOAL).pad(20.0)),
     ]).set(MASTER, ui);
-    set_scoreboard(ui, game.get_game_stats());
-    set_next_piece(ui);
+    set_scoreboard(ui, game);
+    set_next_piece(ui, game.get_settings().preview_count);
+    set_hold_piece(ui);
+    if let Some(clear) = game.get_callout() {
+        set_callout(ui, clear);
+    }
 
-    if game.is_paused() {
+    if game.is_counting_down() {
+        set_countdown(ui, game.get_countdown());
+    } else if game.is_paused() {
         set_pause_menu(ui, game);
     } else if game.is_game_over() {
         set_game_over_menu(ui, game);
+    } else if game.is_finished() {
+        set_finished_menu(ui, game, records);
+    } else if game.is_time_up() {
+        set_time_up_menu(ui, game, records);
     }
 
 }
@@ -49,9 +65,14 @@ fn set_pause_menu(ui: &mut UICell, game: &mut Rustris) {
       .rgba(0.0, 0.0, 0.0, 0.3)
       .set(PAUSE_OVERLAY, ui);
 
-    Canvas::new().flow_down(&[
-        (RESUME_CANVAS, Canvas::new())
-    ]).label("Paused")
+    // The start level can be picked here too, so it can be changed before the first game ends
+    let mut canvases = vec![(RESUME_CANVAS, Canvas::new())];
+    if has_start_level(game) {
+        canvases.push((PAUSE_START_LEVEL_CANVAS, Canvas::new()));
+    }
+    canvases.push((PAUSE_RESTART_CANVAS, Canvas::new()));
+    Canvas::new().flow_down(&canvases)
+      .label("Paused")
       .label_color(color::WHITE)
       .w_h(200.0, 200.0)
       .frame(1.0)
@@ -70,6 +91,41 @@ fn set_pause_menu(ui: &mut UICell, game: &mut Rustris) {
             game.set_game_state(GameState::Playing);
         })
         .set(RESUME_BUTTON, ui);
+
+    if has_start_level(game) {
+        set_start_level_dialer(ui, game, PAUSE_START_LEVEL_CANVAS, PAUSE_START_LEVEL_DIALER);
+    }
+
+    Button::new()
+        .label("Restart")
+        .label_color(color::WHITE)
+        .color(color::CHARCOAL)
+        .middle_of(PAUSE_RESTART_CANVAS)
+        .w_h(150.0, 30.0)
+        .react(|| {
+            game.reset();
+        })
+        .set(PAUSE_RESTART_BUTTON, ui);
+}
+
+// Master always starts from level 0, every other mode can start higher up.
+fn has_start_level(game: &Rustris) -> bool {
+    game.get_settings().mode != GameMode::Master
+}
+
+// A dialer for the level the next game starts on.
+fn set_start_level_dialer(ui: &mut UICell, game: &mut Rustris, canvas: WidgetId,
+                          dialer: WidgetId) {
+    NumberDialer::new(game.get_settings().start_level as f32, 1.0, MAX_GAME_LEVEL as f32, 0)
+        .label("Start Level")
+        .label_color(color::WHITE)
+        .color(color::CHARCOAL)
+        .middle_of(canvas)
+        .w_h(150.0, 30.0)
+        .react(|level: f32| {
+            game.set_start_level(level as u32);
+        })
+        .set(dialer, ui);
 }
 
 fn set_game_over_menu(ui: &mut UICell, game: &mut Rustris) {
@@ -80,25 +136,60 @@ fn set_game_over_menu(ui: &mut UICell, game: &mut Rustris) {
       .rgba(0.0, 0.0, 0.0, 0.3)
       .set(GAME_OVER_OVERLAY, ui);
 
-    Canvas::new().flow_down(&[
-        (FINAL_SCORE_CANVAS, Canvas::new()),
-        (RESTART_CANVAS, Canvas::new())
-    ]).label("Game Over")
+    // Some modes report more than the score. Survival is about lasting, so it reports how long
+    // the player held on instead.
+    let (result, detail) = {
+        let stats = game.get_game_stats();
+        let score = format!("Final Score: {}", stats.get_score());
+        match game.get_settings().mode {
+            GameMode::Survival => {
+                (format!("Survived: {}", format_time(stats.get_time())),
+                 Some(format!("Garbage Cleared: {}", stats.get_garbage_cleared())))
+            }
+            GameMode::Master => (score, Some(format!("Grade: {}", get_grade(stats)))),
+            _ => (score, None)
+        }
+    };
+    let has_start_level = has_start_level(game);
+    let mut canvases = vec![(TOP_OUT_CANVAS, Canvas::new()), (FINAL_SCORE_CANVAS, Canvas::new())];
+    if detail.is_some() {
+        canvases.push((GAME_OVER_DETAIL_CANVAS, Canvas::new()));
+    }
+    if has_start_level {
+        canvases.push((START_LEVEL_CANVAS, Canvas::new()));
+    }
+    canvases.push((RESTART_CANVAS, Canvas::new()));
+    Canvas::new().flow_down(&canvases)
+      .label("Game Over")
       .label_color(color::WHITE)
-      .w_h(200.0, 200.0)
+      .w_h(200.0, 250.0)
       .frame(1.0)
       .frame_color(color::WHITE)
       .pad(1.0)
       .middle_of(GAME_OVER_OVERLAY)
       .set(GAME_OVER_MENU, ui);
 
-    {
-        let stats = game.get_game_stats();
-        Text::new(
-            &format!("Final Score: {}", stats.get_score().to_string()))
+    if let Some(top_out) = game.get_top_out() {
+        Text::new(top_out_description(top_out))
+            .color(color::WHITE)
+            .middle_of(TOP_OUT_CANVAS)
+            .set(TOP_OUT_TEXT, ui);
+    }
+
+    Text::new(&result)
+        .color(color::WHITE)
+        .middle_of(FINAL_SCORE_CANVAS)
+        .set(FINAL_SCORE_TEXT, ui);
+
+    if let Some(ref detail) = detail {
+        Text::new(detail)
             .color(color::WHITE)
-            .middle_of(FINAL_SCORE_CANVAS)
-            .set(FINAL_SCORE_TEXT, ui);
+            .middle_of(GAME_OVER_DETAIL_CANVAS)
+            .set(GAME_OVER_DETAIL_TEXT, ui);
+    }
+
+    if has_start_level {
+        set_start_level_dialer(ui, game, START_LEVEL_CANVAS, START_LEVEL_DIALER);
     }
 
     Button::new()
@@ -113,37 +204,248 @@ fn set_game_over_menu(ui: &mut UICell, game: &mut Rustris) {
         .set(NEW_GAME_BUTTON, ui);
 }
 
-fn set_scoreboard(ui: &mut UICell, stats: &GameStats) {
+fn set_countdown(ui: &mut UICell, countdown: f64) {
+    Text::new(&countdown.ceil().to_string())
+        .color(color::WHITE)
+        .font_size(64)
+        .middle_of(MIDDLE_COLUMN)
+        .set(COUNTDOWN_TEXT, ui);
+}
+
+fn set_finished_menu(ui: &mut UICell, game: &mut Rustris, records: &Records) {
+    Canvas::new()
+      .w_h(WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64)
+      .floating(true)
+      .middle_of(MASTER)
+      .rgba(0.0, 0.0, 0.0, 0.3)
+      .set(FINISHED_OVERLAY, ui);
+
+    let mode = game.get_settings().mode;
+    let (title, results) = match mode {
+        GameMode::Sprint | GameMode::Dig(_) => ("Finished!", race_results(game, records)),
+        GameMode::Master => ("Finished!", master_results(game, records)),
+        _ => ("Congratulations!", marathon_results(game))
+    };
+    let section_times = if mode == GameMode::Master {
+        game.get_game_stats().get_section_times()
+    } else {
+        Vec::new()
+    };
+    let rows = [
+        (RESULT_CANVAS_1, RESULT_1),
+        (RESULT_CANVAS_2, RESULT_2),
+        (RESULT_CANVAS_3, RESULT_3),
+        (RESULT_CANVAS_4, RESULT_4),
+        (RESULT_CANVAS_5, RESULT_5)
+    ];
+    let mut canvases: Vec<_> =
+        rows.iter().take(results.len()).map(|&(canvas, _)| (canvas, Canvas::new())).collect();
+    let section_times_height = section_times.len() as f64 * SECTION_TIME_HEIGHT;
+    if !section_times.is_empty() {
+        canvases.push((SECTION_TIMES_CANVAS, Canvas::new().length(section_times_height)));
+    }
+    canvases.push((FINISHED_RESTART_CANVAS, Canvas::new()));
+    Canvas::new().flow_down(&canvases)
+      .label(title)
+      .label_color(color::WHITE)
+      .w_h(220.0, 300.0 + section_times_height)
+      .frame(1.0)
+      .frame_color(color::WHITE)
+      .pad(1.0)
+      .middle_of(FINISHED_OVERLAY)
+      .set(FINISHED_MENU, ui);
+
+    for (&(canvas, text), result) in rows.iter().zip(results.iter()) {
+        Text::new(result)
+            .color(color::WHITE)
+            .middle_of(canvas)
+            .set(text, ui);
+    }
+
+    if !section_times.is_empty() {
+        let lines: Vec<String> = section_times.iter().enumerate().map(|(i, &time)| {
+            let start = i as u32 * SECTION_LEVELS;
+            format!("{:03}-{:03}: {}", start, start + SECTION_LEVELS - 1, format_time(time))
+        }).collect();
+        Text::new(&lines.join("\n"))
+            .color(color::WHITE)
+            .font_size(14)
+            .middle_of(SECTION_TIMES_CANVAS)
+            .set(SECTION_TIMES_TEXT, ui);
+    }
+
+    Button::new()
+        .label("New Game?")
+        .label_color(color::WHITE)
+        .color(color::CHARCOAL)
+        .middle_of(FINISHED_RESTART_CANVAS)
+        .w_h(150.0, 30.0)
+        .react(|| {
+            game.reset();
+        })
+        .set(FINISHED_NEW_GAME_BUTTON, ui);
+}
+
+fn race_results(game: &Rustris, records: &Records) -> Vec<String> {
+    let stats = game.get_game_stats();
+    vec![
+        format!("Time: {}", format_time(stats.get_time())),
+        format!("Pieces: {}", stats.get_pieces()),
+        format!("PPS: {:.2}", stats.get_pieces_per_second()),
+        format!("KPP: {:.2}", stats.get_keys_per_piece()),
+        personal_best(game, records)
+    ]
+}
+
+// Compares a finished game's time with the best from before it, since by now it has been recorded.
+fn personal_best(game: &Rustris, records: &Records) -> String {
+    let time = game.get_game_stats().get_time();
+    match records.get_previous_best_time() {
+        Some(best) if best <= time => format!("Personal Best: {}", format_time(best)),
+        Some(best) => format!("New Best! Was {}", format_time(best)),
+        None => "New Personal Best!".to_string()
+    }
+}
+
+fn marathon_results(game: &Rustris) -> Vec<String> {
+    let stats = game.get_game_stats();
+    vec![
+        format!("Final Score: {}", stats.get_score()),
+        format!("Lines: {}", stats.get_lines()),
+        format!("Level: {}", stats.get_level()),
+        format!("Time: {}", format_time(stats.get_time())),
+        format!("PPS: {:.2}", stats.get_pieces_per_second())
+    ]
+}
+
+fn master_results(game: &Rustris, records: &Records) -> Vec<String> {
+    let stats = game.get_game_stats();
+    vec![
+        format!("Grade: {}", get_grade(stats)),
+        format!("Final Score: {}", stats.get_score()),
+        format!("Time: {}", format_time(stats.get_time())),
+        personal_best(game, records)
+    ]
+}
+
+fn set_time_up_menu(ui: &mut UICell, game: &mut Rustris, records: &Records) {
+    Canvas::new()
+      .w_h(WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64)
+      .floating(true)
+      .middle_of(MASTER)
+      .rgba(0.0, 0.0, 0.0, 0.3)
+      .set(TIME_UP_OVERLAY, ui);
+
     Canvas::new().flow_down(&[
-        (SCORE_CANVAS, Canvas::new().label("Score").label_color(color::WHITE)),
-        (LEVEL_CANVAS, Canvas::new().label("Level").label_color(color::WHITE)),
-        (LINES_CANVAS, Canvas::new().label("Lines").label_color(color::WHITE))
-    ]).w_h(150.0, 250.0)
+        (TIME_UP_SCORE_CANVAS, Canvas::new()),
+        (LEADERBOARD_CANVAS, Canvas::new().length(LEADERBOARD_SIZE as f64 * 20.0)),
+        (TIME_UP_RESTART_CANVAS, Canvas::new())
+    ]).label("Time's Up!")
+      .label_color(color::WHITE)
+      .w_h(220.0, 300.0)
       .frame(1.0)
       .frame_color(color::WHITE)
       .pad(1.0)
-      .mid_bottom_of(LEFT_COLUMN)
-      .set(SCOREBOARD, ui);
+      .middle_of(TIME_UP_OVERLAY)
+      .set(TIME_UP_MENU, ui);
 
-    Text::new(&stats.get_score().to_string())
+    let score = game.get_game_stats().get_score();
+    Text::new(&format!("Final Score: {}", score))
         .color(color::WHITE)
-        .middle_of(SCORE_CANVAS)
-        .set(SCORE, ui);
-    Text::new(&stats.get_level().to_string())
-        .color(color::WHITE)
-        .middle_of(LEVEL_CANVAS)
-        .set(LEVEL, ui);
-    Text::new(&stats.get_lines().to_string())
+        .middle_of(TIME_UP_SCORE_CANVAS)
+        .set(TIME_UP_SCORE_TEXT, ui);
+
+    let leaderboard: Vec<String> = records.get_leaderboard(&record_name(game)).iter()
+        .enumerate()
+        .map(|(i, score)| format!("{}. {}", i + 1, score))
+        .collect();
+    Text::new(&leaderboard.join("\n"))
         .color(color::WHITE)
-        .middle_of(LINES_CANVAS)
-        .set(LINES, ui);
+        .font_size(14)
+        .middle_of(LEADERBOARD_CANVAS)
+        .set(LEADERBOARD_TEXT, ui);
+
+    Button::new()
+        .label("New Game?")
+        .label_color(color::WHITE)
+        .color(color::CHARCOAL)
+        .middle_of(TIME_UP_RESTART_CANVAS)
+        .w_h(150.0, 30.0)
+        .react(|| {
+            game.reset();
+        })
+        .set(TIME_UP_NEW_GAME_BUTTON, ui);
+}
+
+fn top_out_description(top_out: TopOut) -> &'static str {
+    match top_out {
+        TopOut::BlockOut => "Block Out",
+        TopOut::LockOut => "Lock Out",
+        TopOut::PartialLockOut => "Partial Lock Out",
+        TopOut::GarbageOut => "Pushed Out"
+    }
 }
 
-fn set_next_piece(ui: &mut UICell) {
+fn set_scoreboard(ui: &mut UICell, game: &Rustris) {
+    let stats = game.get_game_stats();
+    let lines = match game.get_lines_remaining() {
+        Some(remaining) => format!("{} ({} left)", stats.get_lines(), remaining),
+        None => stats.get_lines().to_string()
+    };
+    let garbage = match game.get_garbage_remaining() {
+        Some(remaining) => format!("{} ({} left)", stats.get_garbage_cleared(), remaining),
+        None => stats.get_garbage_cleared().to_string()
+    };
+    // Master shows the level the counter will stop at next, like the arcade
+    let level = match game.get_settings().mode {
+        GameMode::Master => format!("{}/{}", stats.get_level(), get_level_stop(stats.get_level())),
+        _ => stats.get_level().to_string()
+    };
+    // Timed modes count down to the end of the game
+    let time = game.get_time_remaining().unwrap_or(stats.get_time());
+    let rows = [
+        (SCORE_CANVAS, SCORE, "Score", stats.get_score().to_string()),
+        (LEVEL_CANVAS, LEVEL, "Level", level),
+        (NEXT_LEVEL_CANVAS, NEXT_LEVEL, "Next Level", stats.get_lines_to_next_level().to_string()),
+        (LINES_CANVAS, LINES, "Lines", lines),
+        (BACK_TO_BACK_CANVAS, BACK_TO_BACK, "Back-to-Back", stats.get_back_to_back().to_string()),
+        (COMBO_CANVAS, COMBO, "Combo", stats.get_combo().to_string()),
+        (PERFECT_CLEARS_CANVAS, PERFECT_CLEARS, "Perfect Clears",
+         stats.get_perfect_clears().to_string()),
+        (GARBAGE_CANVAS, GARBAGE, "Garbage", garbage),
+        (TIME_CANVAS, TIME, "Time", format_time(time)),
+        (SEED_CANVAS, SEED, "Seed", game.get_seed().to_string())
+    ];
+    let canvases: Vec<_> = rows.iter().map(|&(canvas, _, _, _)| (canvas, Canvas::new())).collect();
+    Canvas::new().flow_down(&canvases)
+      .w_h(150.0, rows.len() as f64 * SCOREBOARD_ROW_HEIGHT)
+      .frame(1.0)
+      .frame_color(color::WHITE)
+      .pad(1.0)
+      .mid_bottom_of(LEFT_COLUMN)
+      .set(SCOREBOARD, ui);
+
+    for &(canvas, text, label, ref value) in rows.iter() {
+        Text::new(&format!("{}: {}", label, value))
+            .color(color::WHITE)
+            .font_size(14)
+            .middle_of(canvas)
+            .set(text, ui);
+    }
+}
+
+// Formats a time in seconds as minutes, seconds and milliseconds.
+fn format_time(seconds: f64) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+    format!("{}:{:02}.{:03}", millis / 60000, millis / 1000 % 60, millis % 1000)
+}
+
+fn set_next_piece(ui: &mut UICell, preview_count: usize) {
+    let height = NEXT_PIECE_HEIGHT + (preview_count - 1) as f64 * NEXT_PIECE_SPACING;
     Canvas::new()
         .label("Next Piece")
         .label_color(color::WHITE)
-        .w_h(NEXT_PIECE_WIDTH, NEXT_PIECE_HEIGHT)
+        .w_h(NEXT_PIECE_WIDTH, height)
         .frame(1.0)
         .frame_color(color::WHITE)
         .pad(1.0)
@@ -151,6 +453,50 @@ fn set_next_piece(ui: &mut UICell) {
         .set(NEXT_PIECE, ui);
 }
 
+fn set_hold_piece(ui: &mut UICell) {
+    Canvas::new()
+        .label("Hold")
+        .label_color(color::WHITE)
+        .w_h(NEXT_PIECE_WIDTH, NEXT_PIECE_HEIGHT)
+        .frame(1.0)
+        .frame_color(color::WHITE)
+        .pad(1.0)
+        .mid_top_of(LEFT_COLUMN)
+        .set(HOLD_PIECE, ui);
+}
+
+// Announces a line clear or T-spin under the hold piece.
+fn set_callout(ui: &mut UICell, clear: &LineClear) {
+    Text::new(&callout_text(clear))
+        .color(color::WHITE)
+        .down_from(HOLD_PIECE, 30.0)
+        .align_middle_x_of(HOLD_PIECE)
+        .set(CALLOUT, ui);
+    if clear.perfect_clear {
+        Text::new("Perfect Clear!")
+            .color(color::YELLOW)
+            .down_from(CALLOUT, 10.0)
+            .align_middle_x_of(HOLD_PIECE)
+            .set(PERFECT_CLEAR_CALLOUT, ui);
+    }
+}
+
+fn callout_text(clear: &LineClear) -> String {
+    let t_spin = match clear.t_spin {
+        TSpin::Full => "T-Spin",
+        TSpin::Mini => "T-Spin Mini",
+        TSpin::None => ""
+    };
+    let lines = match clear.lines {
+        1 => "Single",
+        2 => "Double",
+        3 => "Triple",
+        4 => "Tetris",
+        _ => ""
+    };
+    format!("{} {}", t_spin, lines).trim().to_string()
+}
+
 widget_ids! {
     // Canvas IDs
     MASTER,
@@ -162,16 +508,34 @@ widget_ids! {
     SCOREBOARD,
     SCORE_CANVAS,
     LEVEL_CANVAS,
+    NEXT_LEVEL_CANVAS,
     LINES_CANVAS,
+    BACK_TO_BACK_CANVAS,
+    COMBO_CANVAS,
+    PERFECT_CLEARS_CANVAS,
+    GARBAGE_CANVAS,
+    TIME_CANVAS,
+    SEED_CANVAS,
     SCORE,
     LEVEL,
+    NEXT_LEVEL,
     LINES,
+    BACK_TO_BACK,
+    COMBO,
+    PERFECT_CLEARS,
+    GARBAGE,
+    TIME,
+    SEED,
 
     // Pause Menu
     PAUSE_OVERLAY,
     PAUSE_MENU,
     RESUME_CANVAS,
     RESUME_BUTTON,
+    PAUSE_START_LEVEL_CANVAS,
+    PAUSE_START_LEVEL_DIALER,
+    PAUSE_RESTART_CANVAS,
+    PAUSE_RESTART_BUTTON,
 
     // Game Over Menu
     GAME_OVER_OVERLAY,
@@ -180,7 +544,51 @@ widget_ids! {
     RESTART_CANVAS,
     FINAL_SCORE_CANVAS,
     FINAL_SCORE_TEXT,
+    TOP_OUT_CANVAS,
+    TOP_OUT_TEXT,
+    GAME_OVER_DETAIL_CANVAS,
+    GAME_OVER_DETAIL_TEXT,
+    START_LEVEL_CANVAS,
+    START_LEVEL_DIALER,
+
+    // Countdown IDs
+    COUNTDOWN_TEXT,
+
+    // Finished Menu
+    FINISHED_OVERLAY,
+    FINISHED_MENU,
+    RESULT_CANVAS_1,
+    RESULT_CANVAS_2,
+    RESULT_CANVAS_3,
+    RESULT_CANVAS_4,
+    RESULT_CANVAS_5,
+    RESULT_1,
+    RESULT_2,
+    RESULT_3,
+    RESULT_4,
+    RESULT_5,
+    SECTION_TIMES_CANVAS,
+    SECTION_TIMES_TEXT,
+    FINISHED_RESTART_CANVAS,
+    FINISHED_NEW_GAME_BUTTON,
+
+    // Time Up Menu
+    TIME_UP_OVERLAY,
+    TIME_UP_MENU,
+    TIME_UP_SCORE_CANVAS,
+    TIME_UP_SCORE_TEXT,
+    LEADERBOARD_CANVAS,
+    LEADERBOARD_TEXT,
+    TIME_UP_RESTART_CANVAS,
+    TIME_UP_NEW_GAME_BUTTON,
 
     // Next Piece IDs
-    NEXT_PIECE
+    NEXT_PIECE,
+
+    // Hold Piece IDs
+    HOLD_PIECE,
+
+    // Line clear announcements
+    CALLOUT,
+    PERFECT_CLEAR_CALLOUT
 }